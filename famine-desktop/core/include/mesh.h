@@ -1,12 +0,0 @@
-#pragma once
-
-class Mesh {
-public:
-    Mesh(float* vertices) 
-        : vertices(vertices)
-    {
-    }
-
-private:
-    float* vertices;
-};
\ No newline at end of file
@@ -2,9 +2,18 @@
 
 #include <stddef.h>
 
-#include "mesh.h"
-
 class WindowImpl;
+class MeshImpl;
+
+// One attribute of famine::vertex::VertexLayout. `type` is a GL type enum.
+struct VertexAttribute
+{
+    const char* name;
+    int components;
+    unsigned int type;
+    bool normalized;
+    size_t offset;
+};
 
 extern "C"
 {
@@ -13,5 +22,25 @@ extern "C"
     bool window_should_close(WindowImpl* window);
     void window_clear(WindowImpl* window, float r, float g, float b, float a);
     void window_process(WindowImpl* window);
-    void window_draw_mesh(WindowImpl* window, Mesh* mesh);
-}
\ No newline at end of file
+    int window_display_width(WindowImpl* window);
+    int window_display_height(WindowImpl* window);
+
+    unsigned int window_create_shader(WindowImpl* window, const char* vert_src, const char* frag_src);
+    void window_use_shader(WindowImpl* window, unsigned int program);
+    // Active attributes of the program, with the longest name's buffer size.
+    int window_shader_attribute_count(WindowImpl* window, unsigned int program, int* max_length);
+    // Writes attribute `index`'s name to `name` and returns its length.
+    int window_shader_attribute_name(WindowImpl* window, unsigned int program, int index, char* name, int capacity);
+    void window_set_uniform_vec4(WindowImpl* window, unsigned int program, const char* name, const float* value);
+    void window_set_uniform_mat4(WindowImpl* window, unsigned int program, const char* name, const float* value);
+
+    unsigned int window_create_texture(WindowImpl* window, int width, int height, const unsigned char* data);
+    void window_use_texture(WindowImpl* window, unsigned int texture);
+
+    MeshImpl* window_create_mesh(WindowImpl* window, const float* vertices, size_t vertex_count, const unsigned int* indices, size_t index_count,
+        const VertexAttribute* attributes, size_t attribute_count, size_t stride);
+    void window_draw_mesh_buffer(WindowImpl* window, MeshImpl* mesh);
+    void window_destroy_mesh(WindowImpl* window, MeshImpl* mesh);
+    void window_draw_mesh(WindowImpl* window, const float* vertices, size_t vertex_count, const unsigned int* indices, size_t index_count,
+        const VertexAttribute* attributes, size_t attribute_count, size_t stride);
+}
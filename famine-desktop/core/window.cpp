@@ -2,16 +2,110 @@
 #include <GLFW/glfw3.h>
 
 #include <iostream>
+#include <string>
+#include <vector>
 
 #include "include/window.h"
 
+// An owned copy of a VertexAttribute.
+struct Attribute
+{
+    std::string name;
+    GLint components;
+    GLenum type;
+    bool normalized;
+    size_t offset;
+
+    bool operator==(const Attribute& other) const
+    {
+        return name == other.name && components == other.components && type == other.type
+            && normalized == other.normalized && offset == other.offset;
+    }
+};
+
+class MeshImpl
+{
+public:
+    MeshImpl()
+    {
+        glGenVertexArrays(1, &vao);
+        glGenBuffers(1, &vertex_buffer);
+        glGenBuffers(1, &index_buffer);
+
+        // The element array binding is part of the vao.
+        glBindVertexArray(vao);
+        glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, index_buffer);
+    }
+
+    ~MeshImpl()
+    {
+        glDeleteBuffers(1, &vertex_buffer);
+        glDeleteBuffers(1, &index_buffer);
+        glDeleteVertexArrays(1, &vao);
+    }
+
+    void fill(const float* vertices, size_t vertex_count, const unsigned int* indices, size_t index_count,
+        const VertexAttribute* attributes, size_t attribute_count, size_t stride, GLenum usage)
+    {
+        glBindVertexArray(vao);
+        glBindBuffer(GL_ARRAY_BUFFER, vertex_buffer);
+        glBufferData(GL_ARRAY_BUFFER, vertex_count * stride, vertices, usage);
+        if (index_count > 0)
+            glBufferData(GL_ELEMENT_ARRAY_BUFFER, index_count * sizeof(unsigned int), indices, usage);
+
+        indexed = index_count > 0;
+        count = indexed ? index_count : vertex_count;
+
+        std::vector<Attribute> new_layout;
+        for (size_t i = 0; i < attribute_count; i++)
+        {
+            const VertexAttribute& a = attributes[i];
+            new_layout.push_back({a.name, a.components, a.type, a.normalized, a.offset});
+        }
+        if (new_layout != layout || stride != this->stride)
+        {
+            layout = new_layout;
+            this->stride = stride;
+            program = 0;
+        }
+    }
+
+    const Attribute* find(const std::string& name) const
+    {
+        for (const Attribute& attribute : layout)
+        {
+            if (attribute.name == name)
+                return &attribute;
+        }
+        return nullptr;
+    }
+
+    GLuint vao = 0;
+    GLuint vertex_buffer = 0;
+    GLuint index_buffer = 0;
+    size_t count = 0;
+    bool indexed = false;
+    std::vector<Attribute> layout;
+    size_t stride = 0;
+    // The program the attribute pointers were last set up for.
+    GLuint program = 0;
+};
+
 class WindowImpl
 {
 public:
-    WindowImpl(GLFWwindow* glfw_window) : glfw_window(glfw_window)
+    WindowImpl(GLFWwindow* glfw_window, MeshImpl* stream_mesh)
+        : glfw_window(glfw_window), stream_mesh(stream_mesh)
     {}
 
     GLFWwindow* glfw_window;
+    // Reused by window_draw_mesh for meshes that only live for one draw.
+    MeshImpl* stream_mesh;
+    GLuint current_program = 0;
+    // Everything created through window_create_shader and window_create_texture,
+    // deleted with the window.
+    std::vector<GLuint> programs;
+    std::vector<GLuint> textures;
 };
 
 void error_callback(int code, const char* description)
@@ -19,9 +113,63 @@ void error_callback(int code, const char* description)
     std::cerr << "GLFW Error: " << description << "\n";
 }
 
+GLuint compile_shader(GLenum shader_type, const char* source)
+{
+    GLuint shader = glCreateShader(shader_type);
+    glShaderSource(shader, 1, &source, NULL);
+    glCompileShader(shader);
+
+    GLint status = GL_FALSE;
+    glGetShaderiv(shader, GL_COMPILE_STATUS, &status);
+    if (status != GL_TRUE)
+    {
+        GLint length = 0;
+        glGetShaderiv(shader, GL_INFO_LOG_LENGTH, &length);
+        std::vector<char> log(length + 1);
+        glGetShaderInfoLog(shader, length, NULL, log.data());
+        std::cerr << "Failed to compile shader: " << log.data() << "\n";
+    }
+
+    return shader;
+}
+
+void bind_attributes(MeshImpl* mesh, GLuint program)
+{
+    glBindBuffer(GL_ARRAY_BUFFER, mesh->vertex_buffer);
+
+    GLint attribute_count = 0;
+    GLint max_length = 0;
+    glGetProgramiv(program, GL_ACTIVE_ATTRIBUTES, &attribute_count);
+    glGetProgramiv(program, GL_ACTIVE_ATTRIBUTE_MAX_LENGTH, &max_length);
+
+    std::vector<char> name(max_length + 1);
+    for (GLint i = 0; i < attribute_count; i++)
+    {
+        GLint size = 0;
+        GLenum type = 0;
+        glGetActiveAttrib(program, i, name.size(), NULL, &size, &type, name.data());
+        GLint location = glGetAttribLocation(program, name.data());
+        if (location < 0)
+            continue;
+
+        const Attribute* attribute = mesh->find(name.data());
+        if (attribute != nullptr)
+        {
+            glVertexAttribPointer(location, attribute->components, attribute->type, attribute->normalized ? GL_TRUE : GL_FALSE,
+                mesh->stride, (void*)attribute->offset);
+            glEnableVertexAttribArray(location);
+        }
+        else
+        {
+            // Left over from a previous program or layout.
+            glDisableVertexAttribArray(location);
+        }
+    }
+}
+
 extern "C"
 {
-    WindowImpl* window_create(const char* title, size_t width, size_t height) 
+    WindowImpl* window_create(const char* title, size_t width, size_t height)
     {
         if (!glfwInit())
         {
@@ -31,6 +179,12 @@ extern "C"
 
         glfwSetErrorCallback(error_callback);
 
+        // 4.3 core accepts the "#version 300 es" shaders shared with the web backend.
+        glfwWindowHint(GLFW_CONTEXT_VERSION_MAJOR, 4);
+        glfwWindowHint(GLFW_CONTEXT_VERSION_MINOR, 3);
+        glfwWindowHint(GLFW_OPENGL_PROFILE, GLFW_OPENGL_CORE_PROFILE);
+        glfwWindowHint(GLFW_OPENGL_FORWARD_COMPAT, GL_TRUE);
+
         GLFWwindow* glfw_window = glfwCreateWindow(width, height, title, NULL, NULL);
         if (!glfw_window)
         {
@@ -39,7 +193,8 @@ extern "C"
         }
 
         glfwMakeContextCurrent(glfw_window);
-        
+
+        glewExperimental = GL_TRUE;
         auto glew_status = glewInit();
         if (glew_status != GLEW_OK)
         {
@@ -49,12 +204,21 @@ extern "C"
 
         glfwSwapInterval(1);
 
-        WindowImpl* p = new WindowImpl(glfw_window);
+        glEnable(GL_CULL_FACE);
+        glEnable(GL_BLEND);
+        glBlendFunc(GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA);
+
+        WindowImpl* p = new WindowImpl(glfw_window, new MeshImpl());
         return p;
     }
 
     void window_destroy(WindowImpl* window)
     {
+        delete window->stream_mesh;
+        for (GLuint program : window->programs)
+            glDeleteProgram(program);
+        glDeleteTextures(window->textures.size(), window->textures.data());
+
         if (window->glfw_window != nullptr)
             glfwDestroyWindow(window->glfw_window);
 
@@ -69,6 +233,10 @@ extern "C"
 
     void window_clear(WindowImpl* window, float r, float g, float b, float a)
     {
+        int width, height;
+        glfwGetFramebufferSize(window->glfw_window, &width, &height);
+        glViewport(0, 0, width, height);
+
         glClearColor(r, g, b, a);
         glClear(GL_COLOR_BUFFER_BIT);
     }
@@ -79,8 +247,142 @@ extern "C"
         glfwPollEvents();
     }
 
-    void window_draw_mesh(WindowImpl* window, Mesh* mesh)
+    int window_display_width(WindowImpl* window)
     {
-        
+        int width, height;
+        glfwGetFramebufferSize(window->glfw_window, &width, &height);
+        return width;
     }
-}
\ No newline at end of file
+
+    int window_display_height(WindowImpl* window)
+    {
+        int width, height;
+        glfwGetFramebufferSize(window->glfw_window, &width, &height);
+        return height;
+    }
+
+    unsigned int window_create_shader(WindowImpl* window, const char* vert_src, const char* frag_src)
+    {
+        GLuint vert_shader = compile_shader(GL_VERTEX_SHADER, vert_src);
+        GLuint frag_shader = compile_shader(GL_FRAGMENT_SHADER, frag_src);
+
+        GLuint program = glCreateProgram();
+        glAttachShader(program, vert_shader);
+        glAttachShader(program, frag_shader);
+        glLinkProgram(program);
+
+        GLint status = GL_FALSE;
+        glGetProgramiv(program, GL_LINK_STATUS, &status);
+        if (status != GL_TRUE)
+        {
+            GLint length = 0;
+            glGetProgramiv(program, GL_INFO_LOG_LENGTH, &length);
+            std::vector<char> log(length + 1);
+            glGetProgramInfoLog(program, length, NULL, log.data());
+            std::cerr << "Error creating shader program: " << log.data() << "\n";
+        }
+
+        glDetachShader(program, vert_shader);
+        glDetachShader(program, frag_shader);
+        glDeleteShader(vert_shader);
+        glDeleteShader(frag_shader);
+
+        window->programs.push_back(program);
+        return program;
+    }
+
+    void window_use_shader(WindowImpl* window, unsigned int program)
+    {
+        glUseProgram(program);
+        window->current_program = program;
+    }
+
+    int window_shader_attribute_count(WindowImpl* window, unsigned int program, int* max_length)
+    {
+        GLint count = 0;
+        glGetProgramiv(program, GL_ACTIVE_ATTRIBUTES, &count);
+        glGetProgramiv(program, GL_ACTIVE_ATTRIBUTE_MAX_LENGTH, max_length);
+        return count;
+    }
+
+    int window_shader_attribute_name(WindowImpl* window, unsigned int program, int index, char* name, int capacity)
+    {
+        GLsizei length = 0;
+        GLint size = 0;
+        GLenum type = 0;
+        glGetActiveAttrib(program, index, capacity, &length, &size, &type, name);
+        return length;
+    }
+
+    void window_set_uniform_vec4(WindowImpl* window, unsigned int program, const char* name, const float* value)
+    {
+        glProgramUniform4fv(program, glGetUniformLocation(program, name), 1, value);
+    }
+
+    void window_set_uniform_mat4(WindowImpl* window, unsigned int program, const char* name, const float* value)
+    {
+        glProgramUniformMatrix4fv(program, glGetUniformLocation(program, name), 1, GL_FALSE, value);
+    }
+
+    unsigned int window_create_texture(WindowImpl* window, int width, int height, const unsigned char* data)
+    {
+        GLuint texture = 0;
+        glGenTextures(1, &texture);
+
+        glActiveTexture(GL_TEXTURE0);
+        glBindTexture(GL_TEXTURE_2D, texture);
+        glPixelStorei(GL_UNPACK_ALIGNMENT, 1);
+        glTexImage2D(GL_TEXTURE_2D, 0, GL_RGBA, width, height, 0, GL_RGBA, GL_UNSIGNED_BYTE, data);
+        glGenerateMipmap(GL_TEXTURE_2D);
+
+        window->textures.push_back(texture);
+        return texture;
+    }
+
+    void window_use_texture(WindowImpl* window, unsigned int texture)
+    {
+        glActiveTexture(GL_TEXTURE0);
+        glBindTexture(GL_TEXTURE_2D, texture);
+    }
+
+    MeshImpl* window_create_mesh(WindowImpl* window, const float* vertices, size_t vertex_count, const unsigned int* indices, size_t index_count,
+        const VertexAttribute* attributes, size_t attribute_count, size_t stride)
+    {
+        MeshImpl* mesh = new MeshImpl();
+        mesh->fill(vertices, vertex_count, indices, index_count, attributes, attribute_count, stride, GL_STATIC_DRAW);
+        return mesh;
+    }
+
+    void window_draw_mesh_buffer(WindowImpl* window, MeshImpl* mesh)
+    {
+        if (window->current_program == 0)
+        {
+            std::cerr << "Famine Warning: Mesh drawn without a shader in use.\n";
+            return;
+        }
+
+        glBindVertexArray(mesh->vao);
+        if (mesh->program != window->current_program)
+        {
+            bind_attributes(mesh, window->current_program);
+            mesh->program = window->current_program;
+        }
+
+        if (mesh->indexed)
+            glDrawElements(GL_TRIANGLES, mesh->count, GL_UNSIGNED_INT, (void*)0);
+        else
+            glDrawArrays(GL_TRIANGLES, 0, mesh->count);
+    }
+
+    void window_destroy_mesh(WindowImpl* window, MeshImpl* mesh)
+    {
+        delete mesh;
+    }
+
+    void window_draw_mesh(WindowImpl* window, const float* vertices, size_t vertex_count, const unsigned int* indices, size_t index_count,
+        const VertexAttribute* attributes, size_t attribute_count, size_t stride)
+    {
+        window->stream_mesh->fill(vertices, vertex_count, indices, index_count, attributes, attribute_count, stride, GL_STREAM_DRAW);
+        window_draw_mesh_buffer(window, window->stream_mesh);
+    }
+}
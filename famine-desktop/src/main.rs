@@ -1,35 +1,158 @@
-use std::ffi::CString;
-use famine::{Application, Mesh, ContextType};
+use std::{cell::{Cell, RefCell}, ffi::CString, rc::Rc};
+use famine::{assets, executor, linalg::{Mat4, Vec4}, shaders::font_shader::{FONT_FRAG_SHADER, FONT_VERT_SHADER}, vertex::VertexLayout, Color, ContextType, Mesh};
 use famine_application::App;
 
+// VertexAttribute in window.h.
+#[repr(C)]
+struct VertexAttribute {
+    name: *const libc::c_char,
+    components: libc::c_int,
+    kind: libc::c_uint,
+    normalized: bool,
+    offset: libc::size_t,
+}
+
 extern "C" {
     fn window_create(title: *const libc::c_char, width: libc::size_t, height: libc::size_t) -> *const libc::c_void;
     fn window_destroy(window: *const libc::c_void);
     fn window_should_close(window: *const libc::c_void) -> bool;
     fn window_clear(window: *const libc::c_void, r: libc::c_float, g: libc::c_float, b: libc::c_float, a: libc::c_float);
     fn window_process(window: *const libc::c_void);
-    fn window_draw_mesh(window: *const libc::c_void, vertices: *const libc::c_float);
-    fn window_use_shader(window: *const libc::c_void);
+    fn window_display_width(window: *const libc::c_void) -> libc::c_int;
+    fn window_display_height(window: *const libc::c_void) -> libc::c_int;
+    fn window_create_shader(window: *const libc::c_void, vert_src: *const libc::c_char, frag_src: *const libc::c_char) -> libc::c_uint;
+    fn window_use_shader(window: *const libc::c_void, program: libc::c_uint);
+    fn window_shader_attribute_count(window: *const libc::c_void, program: libc::c_uint, max_length: *mut libc::c_int) -> libc::c_int;
+    fn window_shader_attribute_name(window: *const libc::c_void, program: libc::c_uint, index: libc::c_int, name: *mut libc::c_char, capacity: libc::c_int) -> libc::c_int;
+    fn window_set_uniform_vec4(window: *const libc::c_void, program: libc::c_uint, name: *const libc::c_char, value: *const libc::c_float);
+    fn window_set_uniform_mat4(window: *const libc::c_void, program: libc::c_uint, name: *const libc::c_char, value: *const libc::c_float);
+    fn window_create_texture(window: *const libc::c_void, width: libc::c_int, height: libc::c_int, data: *const libc::c_uchar) -> libc::c_uint;
+    fn window_use_texture(window: *const libc::c_void, texture: libc::c_uint);
+    fn window_create_mesh(window: *const libc::c_void, vertices: *const libc::c_float, vertex_count: libc::size_t, indices: *const libc::c_uint, index_count: libc::size_t,
+        attributes: *const VertexAttribute, attribute_count: libc::size_t, stride: libc::size_t) -> *const libc::c_void;
+    fn window_draw_mesh_buffer(window: *const libc::c_void, mesh: *const libc::c_void);
+    fn window_destroy_mesh(window: *const libc::c_void, mesh: *const libc::c_void);
+    fn window_draw_mesh(window: *const libc::c_void, vertices: *const libc::c_float, vertex_count: libc::size_t, indices: *const libc::c_uint, index_count: libc::size_t,
+        attributes: *const VertexAttribute, attribute_count: libc::size_t, stride: libc::size_t);
+}
+
+// Destroys the window once neither the context nor any mesh buffer, which
+// needs the GL context to free its buffers, is left.
+struct WindowHandle(*const libc::c_void);
+
+impl Drop for WindowHandle {
+    fn drop(&mut self) {
+        unsafe { window_destroy(self.0) }
+    }
 }
 
-#[repr(C)]
 struct DesktopWindow {
     __impl: *const libc::c_void,
+    handle: Rc<WindowHandle>,
+    font_texture: Option<DesktopTexture>,
+    font_shader: Option<DesktopShader>,
+    // The program in use and the attributes it reads.
+    current_shader: RefCell<Option<(libc::c_uint, Vec<String>)>>,
+    // What draw_mesh last drew, and the program it was checked against.
+    stream_layout: RefCell<Option<VertexLayout>>,
+    stream_checked: Cell<libc::c_uint>,
 }
 
 struct DesktopShader {
+    program: libc::c_uint,
+    attributes: Vec<String>,
+}
 
+struct DesktopTexture {
+    texture: libc::c_uint,
+}
+
+struct DesktopMeshBuffer {
+    __impl: *const libc::c_void,
+    window: Rc<WindowHandle>,
+    layout: VertexLayout,
+    // The program the layout was last checked against.
+    checked: Cell<libc::c_uint>,
+}
+
+impl Drop for DesktopMeshBuffer {
+    fn drop(&mut self) {
+        unsafe { window_destroy_mesh(self.window.0, self.__impl) }
+    }
+}
+
+// Calls `f` with the layout as VertexAttributes, whose names live until it returns.
+fn with_attributes<T>(layout: &VertexLayout, f: impl FnOnce(&[VertexAttribute]) -> T) -> T {
+    let names: Vec<CString> = layout.attributes().iter().map(|a| CString::new(a.name.as_str()).unwrap()).collect();
+    let attributes: Vec<VertexAttribute> = layout.offsets().zip(&names)
+        .map(|((offset, attribute), name)| VertexAttribute {
+            name: name.as_ptr(),
+            components: attribute.components as libc::c_int,
+            kind: attribute.kind.gl_enum(),
+            normalized: attribute.normalized,
+            offset,
+        })
+        .collect();
+    f(&attributes)
+}
+
+impl DesktopWindow {
+    // Names of the attributes the program reads, without the gl_ built ins.
+    fn program_attributes(&self, program: libc::c_uint) -> Vec<String> {
+        let mut max_length = 0;
+        let count = unsafe { window_shader_attribute_count(self.__impl, program, &mut max_length) };
+        let mut name = vec![0u8; max_length.max(1) as usize];
+        (0..count)
+            .map(|i| {
+                let length = unsafe {
+                    window_shader_attribute_name(self.__impl, program, i, name.as_mut_ptr() as *mut libc::c_char, name.len() as libc::c_int)
+                };
+                String::from_utf8_lossy(&name[..length.max(0) as usize]).into_owned()
+            })
+            .filter(|name| !name.starts_with("gl_"))
+            .collect()
+    }
+
+    // Warns about attributes the shader in use reads but `layout` lacks, as
+    // the other backends do, once for each program it is drawn with.
+    fn check_layout(&self, layout: &VertexLayout, checked: &Cell<libc::c_uint>) {
+        let current_shader = self.current_shader.borrow();
+        let Some((program, attributes)) = current_shader.as_ref() else {
+            return
+        };
+        if checked.replace(*program) == *program {
+            return
+        }
+        if let Err(err) = layout.validate(attributes.iter().map(String::as_str)) {
+            Self::log(&format!("Famine Warning: {}.", err));
+        }
+    }
 }
 
 impl ContextType for DesktopWindow {
     type Shader = DesktopShader;
+    type Texture = DesktopTexture;
+    type MeshBuffer = DesktopMeshBuffer;
 
     fn new(title: &str, width: usize, height: usize) -> DesktopWindow {
 
         let cstr: CString = CString::new(title).unwrap();
         let raw_title = cstr.as_ptr();
 
-        unsafe { DesktopWindow { __impl: window_create(raw_title, width, height) } }
+        let __impl = unsafe { window_create(raw_title, width, height) };
+        if __impl.is_null() {
+            panic!("Failed to create desktop window")
+        }
+
+        DesktopWindow {
+            __impl,
+            handle: Rc::new(WindowHandle(__impl)),
+            font_texture: None,
+            font_shader: None,
+            current_shader: RefCell::new(None),
+            stream_layout: RefCell::new(None),
+            stream_checked: Cell::new(0),
+        }
     }
 
     fn clear(&self, r: f32, g: f32, b: f32, a: f32) {
@@ -37,28 +160,128 @@ impl ContextType for DesktopWindow {
     }
 
     fn draw_mesh(&self, mesh: &Mesh) {
-        unsafe { window_draw_mesh(self.__impl, mesh.vertices.as_ptr()) }
+        if self.stream_layout.borrow().as_ref() != Some(&mesh.layout) {
+            *self.stream_layout.borrow_mut() = Some(mesh.layout.clone());
+            self.stream_checked.set(0);
+        }
+        self.check_layout(&mesh.layout, &self.stream_checked);
+
+        with_attributes(&mesh.layout, |attributes| unsafe {
+            window_draw_mesh(self.__impl, mesh.vertices.as_ptr(), mesh.vertex_count(), mesh.indices.as_ptr(), mesh.indices.len(),
+                attributes.as_ptr(), attributes.len(), mesh.layout.stride())
+        })
     }
-    
-    fn new_shader(&self, _vert_src: &str, _frag_str: &str) -> Self::Shader {
-        DesktopShader {}
+
+    fn draw_mesh_buffer(&self, mesh: &Self::MeshBuffer) {
+        self.check_layout(&mesh.layout, &mesh.checked);
+        unsafe { window_draw_mesh_buffer(self.__impl, mesh.__impl) }
     }
 
-    fn use_shader(&self, _shader: &Self::Shader) {
-        todo!()
+    fn upload_mesh(&self, mesh: &Mesh) -> Self::MeshBuffer {
+        DesktopMeshBuffer {
+            __impl: with_attributes(&mesh.layout, |attributes| unsafe {
+                window_create_mesh(self.__impl, mesh.vertices.as_ptr(), mesh.vertex_count(), mesh.indices.as_ptr(), mesh.indices.len(),
+                    attributes.as_ptr(), attributes.len(), mesh.layout.stride())
+            }),
+            window: self.handle.clone(),
+            layout: mesh.layout.clone(),
+            checked: Cell::new(0),
+        }
     }
-}
 
-impl Drop for DesktopWindow {
-    fn drop(&mut self) {
-        unsafe { window_destroy( self.__impl ) }
+    fn new_shader(&self, vert_src: &str, frag_src: &str) -> Self::Shader {
+        let vert_src = CString::new(vert_src).unwrap();
+        let frag_src = CString::new(frag_src).unwrap();
+
+        let program = unsafe { window_create_shader(self.__impl, vert_src.as_ptr(), frag_src.as_ptr()) };
+        DesktopShader { program, attributes: self.program_attributes(program) }
+    }
+
+    fn use_shader(&self, shader: &Self::Shader) {
+        unsafe { window_use_shader(self.__impl, shader.program) }
+        *self.current_shader.borrow_mut() = Some((shader.program, shader.attributes.clone()));
+    }
+
+    fn set_uniform_vec4(&self, shader: &Self::Shader, uniform_name: &str, value: &Vec4) {
+        let name = CString::new(uniform_name).unwrap();
+        unsafe { window_set_uniform_vec4(self.__impl, shader.program, name.as_ptr(), value.data.as_ptr()) }
+    }
+
+    fn set_uniform_mat4(&self, shader: &Self::Shader, uniform_name: &str, value: &Mat4) {
+        let name = CString::new(uniform_name).unwrap();
+        unsafe { window_set_uniform_mat4(self.__impl, shader.program, name.as_ptr(), value.data.as_ptr()) }
+    }
+
+    async fn new_image_texture(&self, name: &str) -> Self::Texture {
+        match assets::load_image(name) {
+            Ok(image) => self.new_data_texture(image.width as i32, image.height as i32, image.pixels),
+            Err(err) => {
+                Self::log(&format!("Error loading image texture: {}", err));
+                self.new_data_texture(1, 1, vec![0, 0, 0, 0])
+            }
+        }
+    }
+
+    fn new_data_texture(&self, width: i32, height: i32, data: Vec<u8>) -> Self::Texture {
+        // glTexImage2D reads width * height RGBA pixels whatever the length of `data`.
+        let needed = usize::try_from(width).ok()
+            .zip(usize::try_from(height).ok())
+            .and_then(|(w, h)| w.checked_mul(h)?.checked_mul(4));
+        if needed.is_none_or(|needed| data.len() < needed) {
+            Self::log(&format!("Error creating data texture: {} bytes for a {}x{} texture", data.len(), width, height));
+            return self.new_data_texture(1, 1, vec![0, 0, 0, 0]);
+        }
+
+        DesktopTexture {
+            texture: unsafe { window_create_texture(self.__impl, width, height, data.as_ptr()) },
+        }
+    }
+
+    async fn load_asset(&self, name: &str) -> Result<Vec<u8>, String> {
+        assets::load(name)
+    }
+
+    fn use_texture(&self, texture: &Self::Texture) {
+        unsafe { window_use_texture(self.__impl, texture.texture) }
+    }
+
+    fn display_width(&self) -> i32 {
+        unsafe { window_display_width(self.__impl) }
+    }
+
+    fn display_height(&self) -> i32 {
+        unsafe { window_display_height(self.__impl) }
+    }
+
+    fn log(text: &str) {
+        println!("{}", text);
+    }
+
+    fn draw_text(&self, text: &str, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        if self.font_texture.is_none() {
+            Self::log("Famine Warning: Context is missing font texture.");
+            return
+        }
+
+        self.use_shader(self.font_shader.as_ref().unwrap());
+        self.use_texture(self.font_texture.as_ref().unwrap());
+        self.set_uniform_vec4(self.font_shader.as_ref().unwrap(), "u_color", &color.as_vec4());
+        self.draw_mesh(&Mesh::text(text, x, y, width, height));
+    }
+
+    fn set_font_texture(&mut self, texture: Self::Texture) {
+        if self.font_shader.is_none() {
+            let font_shader = self.new_shader(FONT_VERT_SHADER, FONT_FRAG_SHADER);
+            self.font_shader = Some(font_shader);
+        }
+
+        self.font_texture = Some(texture);
     }
 }
 
 fn main() {
-    let application = App::<DesktopWindow>::new();
-    while !unsafe { window_should_close(application.get_window().__impl) } {
-        application.update();
-        unsafe { window_process(application.get_window().__impl) };
-    }
+    executor::run::<DesktopWindow, App<DesktopWindow>>(|window| unsafe {
+        window_process(window.__impl);
+        !window_should_close(window.__impl)
+    });
 }
@@ -0,0 +1,102 @@
+// Golden image comparisons for applications rendered with `HeadlessContext`.
+//
+// Set FAMINE_UPDATE_GOLDEN=1 to (re)write the stored images from the
+// current output instead of comparing against them.
+
+use std::{fs, path::{Path, PathBuf}};
+
+use famine::{executor::block_on, image::{self, Image}, time::FrameClock, Application};
+
+use crate::{raster::Framebuffer, HeadlessContext};
+
+// Seconds between the frames of `run_frames`, fixed so output is reproducible.
+pub const FRAME_TIME: f64 = 1.0 / 30.0;
+
+// Starts the application and runs `frames` updates.
+pub fn run_frames<App: Application<HeadlessContext>>(frames: usize) -> App {
+    let mut app = block_on(App::new());
+    let mut clock = FrameClock::new();
+    for i in 0..frames {
+        block_on(app.update(clock.tick(i as f64 * FRAME_TIME)));
+    }
+    app
+}
+
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("golden");
+    path.with_file_name(format!("{}.{}.png", stem, suffix))
+}
+
+fn write_png(path: &Path, image: &Image) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    }
+    fs::write(path, image::encode_png(image)).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+// Compares the framebuffer against the PNG at `golden`, allowing each
+// channel to differ by up to `tolerance`. On a mismatch the actual output
+// and a diff image (failing pixels in red) are written next to the golden.
+pub fn compare(framebuffer: &Framebuffer, golden: &Path, tolerance: u8) -> Result<(), String> {
+    let actual = framebuffer.to_image();
+
+    if std::env::var_os("FAMINE_UPDATE_GOLDEN").is_some() {
+        return write_png(golden, &actual);
+    }
+
+    let bytes = fs::read(golden).map_err(|e| {
+        format!("Failed to read golden image {}: {} (run with FAMINE_UPDATE_GOLDEN=1 to create it)", golden.display(), e)
+    })?;
+    let expected = image::decode_png(&bytes).map_err(|e| format!("Failed to decode {}: {}", golden.display(), e))?;
+
+    if (expected.width, expected.height) != (actual.width, actual.height) {
+        write_png(&sibling(golden, "actual"), &actual)?;
+        return Err(format!(
+            "Golden image {} is {}x{} but the framebuffer is {}x{}",
+            golden.display(), expected.width, expected.height, actual.width, actual.height
+        ));
+    }
+
+    let mut diff = Image::new(actual.width, actual.height);
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    for y in 0..actual.height {
+        for x in 0..actual.width {
+            let a = actual.pixel(x, y);
+            let e = expected.pixel(x, y);
+            let difference = a.iter().zip(e.iter()).map(|(a, e)| a.abs_diff(*e)).max().unwrap_or(0);
+            max_difference = max_difference.max(difference);
+
+            if difference > tolerance {
+                mismatched += 1;
+                diff.set_pixel(x, y, [255, 0, 0, 255]);
+            } else {
+                let gray = ((a[0] as u16 + a[1] as u16 + a[2] as u16) / 12) as u8;
+                diff.set_pixel(x, y, [gray, gray, gray, 255]);
+            }
+        }
+    }
+
+    if mismatched == 0 {
+        return Ok(());
+    }
+
+    let actual_path = sibling(golden, "actual");
+    let diff_path = sibling(golden, "diff");
+    write_png(&actual_path, &actual)?;
+    write_png(&diff_path, &diff)?;
+    Err(format!(
+        "{} pixels differ from {} by more than {} (max difference {}), see {} and {}",
+        mismatched, golden.display(), tolerance, max_difference, actual_path.display(), diff_path.display()
+    ))
+}
+
+// Runs `frames` updates of the application and panics if the result does
+// not match the golden image.
+pub fn assert_golden<App: Application<HeadlessContext>>(golden: impl AsRef<Path>, frames: usize, tolerance: u8) {
+    let app = run_frames::<App>(frames);
+    let result = compare(&app.get_window().framebuffer(), golden.as_ref(), tolerance);
+    if let Err(message) = result {
+        panic!("{}", message);
+    }
+}
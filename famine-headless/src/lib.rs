@@ -0,0 +1,318 @@
+use std::{cell::{Ref, RefCell}, collections::HashMap, rc::Rc};
+
+use famine::{assets, shaders::font_shader::{FONT_FRAG_SHADER, FONT_VERT_SHADER}, linalg::{Mat4, Vec4}, Color, ContextType, Mesh};
+
+pub mod golden;
+pub mod raster;
+pub mod recording;
+
+use raster::{Framebuffer, TextureData, Vertex};
+
+pub struct HeadlessContext {
+    framebuffer: RefCell<Framebuffer>,
+    current_shader: RefCell<Option<HeadlessShader>>,
+    current_texture: RefCell<Option<HeadlessTexture>>,
+    font_texture: Option<HeadlessTexture>,
+    font_shader: Option<HeadlessShader>,
+}
+
+// GLSL is not executed. The sources are only scanned for their declarations:
+// the first vertex shader input is the position and the first vec2 input the
+// uv, positions are transformed by the first mat4 uniform of the vertex
+// shader, fragments sample the bound texture when the fragment shader
+// declares a sampler2D, and a vec4 fragment uniform tints the texture alpha
+// the way the font shader does.
+#[derive(Clone)]
+pub struct HeadlessShader {
+    program: Rc<Program>,
+}
+
+struct Program {
+    attributes: Vec<String>,
+    position: Option<String>,
+    uv: Option<String>,
+    transform: Option<String>,
+    tint: Option<String>,
+    textured: bool,
+    uniforms: RefCell<HashMap<String, Uniform>>,
+}
+
+#[derive(Clone, Copy)]
+enum Uniform {
+    Vec4([f32; 4]),
+    Mat4([f32; 16]),
+}
+
+#[derive(Clone)]
+pub struct HeadlessTexture {
+    data: Rc<TextureData>,
+}
+
+// Meshes stay on the CPU, so uploading is only a copy.
+#[derive(Clone)]
+pub struct HeadlessMeshBuffer {
+    mesh: Rc<Mesh>,
+}
+
+impl HeadlessMeshBuffer {
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+}
+
+impl HeadlessTexture {
+    pub fn data(&self) -> &TextureData {
+        &self.data
+    }
+}
+
+// (type, name) of the declarations starting with `qualifier`.
+fn declarations<'a>(src: &'a str, qualifier: &str) -> Vec<(&'a str, &'a str)> {
+    src.lines()
+        .map(|line| line.trim().trim_end_matches(';'))
+        .filter(|line| line.split_whitespace().next() == Some(qualifier))
+        .filter_map(|line| {
+            let mut tokens = line.split_whitespace().rev();
+            let name = tokens.next()?;
+            let ty = tokens.next()?;
+            Some((ty, name))
+        })
+        .collect()
+}
+
+impl HeadlessContext {
+    pub fn framebuffer(&self) -> Ref<'_, Framebuffer> {
+        self.framebuffer.borrow()
+    }
+
+    fn new_texture(width: usize, height: usize, pixels: Vec<u8>) -> HeadlessTexture {
+        HeadlessTexture { data: Rc::new(TextureData { width, height, pixels }) }
+    }
+}
+
+impl Program {
+    fn mat4(&self, name: &Option<String>) -> Option<[f32; 16]> {
+        match self.uniforms.borrow().get(name.as_ref()?) {
+            Some(Uniform::Mat4(m)) => Some(*m),
+            // Unset uniforms are zero in GL.
+            _ => Some([0.0; 16]),
+        }
+    }
+
+    fn vec4(&self, name: &Option<String>) -> Option<[f32; 4]> {
+        match self.uniforms.borrow().get(name.as_ref()?) {
+            Some(Uniform::Vec4(v)) => Some(*v),
+            _ => Some([0.0; 4]),
+        }
+    }
+}
+
+impl ContextType for HeadlessContext {
+    type Shader = HeadlessShader;
+    type Texture = HeadlessTexture;
+    type MeshBuffer = HeadlessMeshBuffer;
+
+    fn new(_title: &str, width: usize, height: usize) -> HeadlessContext {
+        HeadlessContext {
+            framebuffer: RefCell::new(Framebuffer::new(width, height)),
+            current_shader: RefCell::new(None),
+            current_texture: RefCell::new(None),
+            font_texture: None,
+            font_shader: None,
+        }
+    }
+
+    fn new_shader(&self, vert_src: &str, frag_src: &str) -> Self::Shader {
+        let vert_inputs = declarations(vert_src, "in");
+        let vert_uniforms = declarations(vert_src, "uniform");
+        let frag_uniforms = declarations(frag_src, "uniform");
+
+        let program = Program {
+            attributes: vert_inputs.iter().map(|(_, name)| name.to_string()).collect(),
+            position: vert_inputs.first().map(|(_, name)| name.to_string()),
+            uv: vert_inputs.iter().find(|(ty, _)| *ty == "vec2").map(|(_, name)| name.to_string()),
+            transform: vert_uniforms.iter().find(|(ty, _)| *ty == "mat4").map(|(_, name)| name.to_string()),
+            tint: frag_uniforms.iter().find(|(ty, _)| *ty == "vec4").map(|(_, name)| name.to_string()),
+            textured: frag_uniforms.iter().any(|(ty, _)| *ty == "sampler2D"),
+            uniforms: RefCell::new(HashMap::new()),
+        };
+
+        HeadlessShader { program: Rc::new(program) }
+    }
+
+    async fn new_image_texture(&self, name: &str) -> Self::Texture {
+        match assets::load_image(name) {
+            Ok(image) => Self::new_texture(image.width, image.height, image.pixels),
+            Err(err) => {
+                Self::log(&format!("Error loading image texture: {}", err));
+                Self::new_texture(1, 1, vec![0, 0, 0, 0])
+            }
+        }
+    }
+
+    fn new_data_texture(&self, width: i32, height: i32, data: Vec<u8>) -> Self::Texture {
+        // Sampling would otherwise index past the end of `data` on the first draw.
+        let needed = usize::try_from(width).ok()
+            .zip(usize::try_from(height).ok())
+            .and_then(|(w, h)| w.checked_mul(h)?.checked_mul(4));
+        if needed.is_none_or(|needed| data.len() < needed) {
+            Self::log(&format!("Error creating data texture: {} bytes for a {}x{} texture", data.len(), width, height));
+            return Self::new_texture(1, 1, vec![0, 0, 0, 0]);
+        }
+        Self::new_texture(width as usize, height as usize, data)
+    }
+
+    async fn load_asset(&self, name: &str) -> Result<Vec<u8>, String> {
+        assets::load(name)
+    }
+
+    fn upload_mesh(&self, mesh: &Mesh) -> Self::MeshBuffer {
+        HeadlessMeshBuffer { mesh: Rc::new(mesh.clone()) }
+    }
+
+    fn use_shader(&self, shader: &Self::Shader) {
+        *self.current_shader.borrow_mut() = Some(shader.clone());
+    }
+
+    fn set_uniform_vec4(&self, shader: &Self::Shader, uniform_name: &str, value: &Vec4) {
+        shader.program.uniforms.borrow_mut().insert(uniform_name.into(), Uniform::Vec4(value.data));
+    }
+
+    fn set_uniform_mat4(&self, shader: &Self::Shader, uniform_name: &str, value: &Mat4) {
+        shader.program.uniforms.borrow_mut().insert(uniform_name.into(), Uniform::Mat4(value.data));
+    }
+
+    fn set_font_texture(&mut self, texture: Self::Texture) {
+        if self.font_shader.is_none() {
+            let font_shader = self.new_shader(FONT_VERT_SHADER, FONT_FRAG_SHADER);
+            self.font_shader = Some(font_shader);
+        }
+
+        self.font_texture = Some(texture);
+    }
+
+    fn use_texture(&self, texture: &Self::Texture) {
+        *self.current_texture.borrow_mut() = Some(texture.clone());
+    }
+
+    fn clear(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.framebuffer.borrow_mut().clear([r, g, b, a]);
+    }
+
+    fn draw_mesh(&self, mesh: &Mesh) {
+        let current_shader = self.current_shader.borrow();
+        let program = match current_shader.as_ref() {
+            Some(shader) => &shader.program,
+            None => {
+                Self::log("Famine Warning: draw_mesh called without a shader in use.");
+                return
+            }
+        };
+
+        if mesh.layout.vertex_size() == 0 {
+            return
+        }
+        if let Err(err) = mesh.layout.validate(program.attributes.iter().map(String::as_str)) {
+            Self::log(&format!("Famine Warning: {}.", err));
+        }
+
+        // Attributes without data read as (0, 0, 0, 1) in GL.
+        let read = |v: &[f32], name: &Option<String>| {
+            name.as_ref().and_then(|name| mesh.layout.read(v, name)).unwrap_or([0.0, 0.0, 0.0, 1.0])
+        };
+
+        let transform = program.mat4(&program.transform).map(|data| Mat4 { data });
+        let vertices: Vec<Vertex> = mesh.vertices.chunks_exact(mesh.layout.vertex_size())
+            .map(|v| {
+                let position = read(v, &program.position);
+                let uv = read(v, &program.uv);
+                let clip = match &transform {
+                    Some(m) => {
+                        let mut clip = [0.0; 4];
+                        for (i, c) in clip.iter_mut().enumerate() {
+                            *c = (0..4).map(|j| m.get(i, j) * position[j]).sum();
+                        }
+                        clip
+                    }
+                    None => position,
+                };
+                Vertex { clip, uv: [uv[0], uv[1]] }
+            })
+            .collect();
+
+        let vertices: Vec<Vertex> = if mesh.is_indexed() {
+            match mesh.indices.iter().map(|&i| vertices.get(i as usize).copied()).collect() {
+                Some(vertices) => vertices,
+                None => {
+                    Self::log("Famine Warning: Mesh index out of range.");
+                    return
+                }
+            }
+        } else {
+            vertices
+        };
+
+        let current_texture = self.current_texture.borrow();
+        let texture = current_texture.as_ref().map(|t| t.data());
+        let tint = program.vec4(&program.tint);
+        let textured = program.textured;
+
+        raster::draw_triangles(&mut self.framebuffer.borrow_mut(), &vertices, |uv| {
+            let base = match (textured, texture) {
+                (false, _) => [1.0; 4],
+                (true, Some(t)) => t.sample(uv[0], uv[1]),
+                // Sampling an incomplete texture in GL gives opaque black.
+                (true, None) => [0.0, 0.0, 0.0, 1.0],
+            };
+            match tint {
+                Some(tint) => tint.map(|c| c * base[3]),
+                None => base,
+            }
+        });
+    }
+
+    fn draw_mesh_buffer(&self, mesh: &Self::MeshBuffer) {
+        self.draw_mesh(&mesh.mesh);
+    }
+
+    fn draw_text(&self, text: &str, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        if self.font_texture.is_none() {
+            Self::log("Famine Warning: Context is missing font texture.");
+            return
+        }
+
+        self.use_shader(self.font_shader.as_ref().unwrap());
+        self.use_texture(self.font_texture.as_ref().unwrap());
+        self.set_uniform_vec4(self.font_shader.as_ref().unwrap(), "u_color", &color.as_vec4());
+        self.draw_mesh(&Mesh::text(text, x, y, width, height));
+    }
+
+    fn display_width(&self) -> i32 {
+        self.framebuffer.borrow().width as i32
+    }
+
+    fn display_height(&self) -> i32 {
+        self.framebuffer.borrow().height as i32
+    }
+
+    fn log(text: &str) {
+        eprintln!("{}", text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_textures_need_every_pixel() {
+        let context = HeadlessContext::new("", 4, 4);
+        assert_eq!(context.new_data_texture(2, 2, vec![255; 16]).data().width, 2);
+
+        for (width, height, data) in [(2, 2, vec![255; 15]), (-1, 4, vec![]), (i32::MAX, i32::MAX, vec![])] {
+            let texture = context.new_data_texture(width, height, data);
+            assert_eq!((texture.data().width, texture.data().height), (1, 1));
+            assert_eq!(texture.data().sample(0.5, 0.5), [0.0; 4]);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+// CPU triangle rasterization following the WebGL state famine sets up:
+// back faces culled (counter-clockwise front), no depth test and
+// SRC_ALPHA / ONE_MINUS_SRC_ALPHA blending.
+
+use famine::image::Image;
+
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    // RGBA8, rows from the top of the display down.
+    pub pixels: Vec<u8>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = 4 * (y * self.width + x);
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    pub fn to_image(&self) -> Image {
+        Image {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.clone(),
+        }
+    }
+
+    pub fn clear(&mut self, color: [f32; 4]) {
+        let color = color.map(to_u8);
+        self.pixels.chunks_exact_mut(4).for_each(|p| p.copy_from_slice(&color));
+    }
+
+    fn blend(&mut self, x: usize, y: usize, src: [f32; 4]) {
+        let i = 4 * (y * self.width + x);
+        let alpha = src[3].clamp(0.0, 1.0);
+        for (c, value) in src.iter().enumerate() {
+            let dst = self.pixels[i + c] as f32 / 255.0;
+            self.pixels[i + c] = to_u8(value * alpha + dst * (1.0 - alpha));
+        }
+    }
+}
+
+fn to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+pub struct TextureData {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl TextureData {
+    fn texel(&self, x: i64, y: i64) -> [f32; 4] {
+        let x = x.rem_euclid(self.width as i64) as usize;
+        let y = y.rem_euclid(self.height as i64) as usize;
+        let i = 4 * (y * self.width + x);
+        [
+            self.pixels[i] as f32 / 255.0,
+            self.pixels[i + 1] as f32 / 255.0,
+            self.pixels[i + 2] as f32 / 255.0,
+            self.pixels[i + 3] as f32 / 255.0,
+        ]
+    }
+
+    // Bilinear filtering with REPEAT wrapping, the WebGL defaults.
+    pub fn sample(&self, u: f32, v: f32) -> [f32; 4] {
+        if self.width == 0 || self.height == 0 {
+            return [0.0, 0.0, 0.0, 1.0];
+        }
+
+        let x = u * self.width as f32 - 0.5;
+        let y = v * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let t00 = self.texel(x0, y0);
+        let t10 = self.texel(x0 + 1, y0);
+        let t01 = self.texel(x0, y0 + 1);
+        let t11 = self.texel(x0 + 1, y0 + 1);
+
+        let mut out = [0.0; 4];
+        for c in 0..4 {
+            let top = t00[c] + (t10[c] - t00[c]) * fx;
+            let bottom = t01[c] + (t11[c] - t01[c]) * fx;
+            out[c] = top + (bottom - top) * fy;
+        }
+        out
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Vertex {
+    pub clip: [f32; 4],
+    pub uv: [f32; 2],
+}
+
+fn edge(a: [f32; 2], b: [f32; 2], p: [f32; 2]) -> f32 {
+    (b[0] - a[0]) * (p[1] - a[1]) - (b[1] - a[1]) * (p[0] - a[0])
+}
+
+pub fn draw_triangles(framebuffer: &mut Framebuffer, vertices: &[Vertex], shade: impl Fn([f32; 2]) -> [f32; 4]) {
+    for triangle in vertices.chunks_exact(3) {
+        draw_triangle(framebuffer, triangle, &shade);
+    }
+}
+
+fn draw_triangle(framebuffer: &mut Framebuffer, triangle: &[Vertex], shade: &impl Fn([f32; 2]) -> [f32; 4]) {
+    // Only whole triangles in front of the eye are drawn, there is no near plane clipping.
+    if triangle.iter().any(|v| v.clip[3] <= f32::EPSILON) {
+        return;
+    }
+
+    let width = framebuffer.width as f32;
+    let height = framebuffer.height as f32;
+
+    let mut screen = [[0.0; 2]; 3];
+    let mut depth = [0.0; 3];
+    let mut inv_w = [0.0; 3];
+    for (i, v) in triangle.iter().enumerate() {
+        inv_w[i] = 1.0 / v.clip[3];
+        screen[i] = [
+            (v.clip[0] * inv_w[i] + 1.0) * 0.5 * width,
+            (1.0 - v.clip[1] * inv_w[i]) * 0.5 * height,
+        ];
+        depth[i] = v.clip[2] * inv_w[i];
+    }
+
+    // The y flip to screen space turns counter-clockwise front faces clockwise.
+    let area = edge(screen[0], screen[1], screen[2]);
+    if area >= 0.0 {
+        return;
+    }
+
+    let min_x = screen.iter().map(|p| p[0]).fold(f32::INFINITY, f32::min).max(0.0).floor() as usize;
+    let min_y = screen.iter().map(|p| p[1]).fold(f32::INFINITY, f32::min).max(0.0).floor() as usize;
+    let max_x = screen.iter().map(|p| p[0]).fold(f32::NEG_INFINITY, f32::max).min(width).ceil() as usize;
+    let max_y = screen.iter().map(|p| p[1]).fold(f32::NEG_INFINITY, f32::max).min(height).ceil() as usize;
+
+    for y in min_y..max_y {
+        for x in min_x..max_x {
+            let p = [x as f32 + 0.5, y as f32 + 0.5];
+            let b0 = edge(screen[1], screen[2], p) / area;
+            let b1 = edge(screen[2], screen[0], p) / area;
+            let b2 = edge(screen[0], screen[1], p) / area;
+            if b0 < 0.0 || b1 < 0.0 || b2 < 0.0 {
+                continue;
+            }
+
+            let z = b0 * depth[0] + b1 * depth[1] + b2 * depth[2];
+            if !(-1.0..=1.0).contains(&z) {
+                continue;
+            }
+
+            // Perspective correct interpolation.
+            let w0 = b0 * inv_w[0];
+            let w1 = b1 * inv_w[1];
+            let w2 = b2 * inv_w[2];
+            let sum = w0 + w1 + w2;
+            let uv = [
+                (w0 * triangle[0].uv[0] + w1 * triangle[1].uv[0] + w2 * triangle[2].uv[0]) / sum,
+                (w0 * triangle[0].uv[1] + w1 * triangle[1].uv[1] + w2 * triangle[2].uv[1]) / sum,
+            ];
+
+            framebuffer.blend(x, y, shade(uv));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(x: f32, y: f32, z: f32) -> Vertex {
+        Vertex { clip: [x, y, z, 1.0], uv: [0.0, 0.0] }
+    }
+
+    // Covers the whole of the display, counter-clockwise.
+    fn covering(z: f32) -> [Vertex; 3] {
+        [vertex(-1.0, -1.0, z), vertex(3.0, -1.0, z), vertex(-1.0, 3.0, z)]
+    }
+
+    fn drawn(vertices: &[Vertex]) -> usize {
+        let mut framebuffer = Framebuffer::new(4, 4);
+        draw_triangles(&mut framebuffer, vertices, |_| [1.0, 0.0, 0.0, 1.0]);
+        framebuffer.pixels.chunks_exact(4).filter(|p| p[0] == 255).count()
+    }
+
+    #[test]
+    fn back_faces_are_culled() {
+        let [a, b, c] = covering(0.0);
+        assert_eq!(drawn(&[a, b, c]), 16);
+        assert_eq!(drawn(&[a, c, b]), 0);
+
+        // The lower left half of the display, including the pixels whose
+        // centers lie on the diagonal.
+        assert_eq!(drawn(&[vertex(-1.0, -1.0, 0.0), vertex(1.0, -1.0, 0.0), vertex(-1.0, 1.0, 0.0)]), 10);
+    }
+
+    #[test]
+    fn fragments_outside_the_clip_depth_are_dropped() {
+        assert_eq!(drawn(&covering(1.0)), 16);
+        assert_eq!(drawn(&covering(1.5)), 0);
+        assert_eq!(drawn(&covering(-1.5)), 0);
+
+        // Behind the eye.
+        let mut behind = covering(0.0);
+        behind[1].clip[3] = -1.0;
+        assert_eq!(drawn(&behind), 0);
+    }
+
+    #[test]
+    fn blends_by_source_alpha() {
+        let mut framebuffer = Framebuffer::new(2, 2);
+        framebuffer.clear([0.0, 0.0, 1.0, 1.0]);
+        draw_triangles(&mut framebuffer, &covering(0.0), |_| [1.0, 0.0, 0.0, 0.5]);
+        // 0.5 * src + 0.5 * dst, alpha included.
+        assert_eq!(framebuffer.pixel(1, 1), [128, 0, 128, 191]);
+    }
+
+    #[test]
+    fn samples_bilinear_with_repeat() {
+        // Black then white.
+        let texture = TextureData { width: 2, height: 1, pixels: vec![0, 0, 0, 255, 255, 255, 255, 255] };
+        assert_eq!(texture.sample(0.25, 0.5), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(texture.sample(0.75, 0.5), [1.0; 4]);
+        assert_eq!(texture.sample(0.5, 0.5), [0.5, 0.5, 0.5, 1.0]);
+        // The left edge blends with the texel wrapped around from the right.
+        assert_eq!(texture.sample(0.0, 0.5), [0.5, 0.5, 0.5, 1.0]);
+        assert_eq!(texture.sample(1.25, -3.0), texture.sample(0.25, 0.5));
+    }
+}
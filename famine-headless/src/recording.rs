@@ -0,0 +1,265 @@
+// A context that renders nothing and keeps a log of every call made on it.
+
+use std::cell::{Cell, Ref, RefCell};
+
+use famine::{assets, linalg::{Mat4, Vec4}, Color, ContextType, Mesh};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingShader {
+    pub id: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingTexture {
+    pub id: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingMeshBuffer {
+    pub id: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    NewShader { shader: usize },
+    NewImageTexture { texture: usize, name: String },
+    NewDataTexture { texture: usize, width: i32, height: i32 },
+    LoadAsset { name: String },
+    UploadMesh { mesh: usize, vertex_count: usize, index_count: usize },
+    UseShader { shader: usize },
+    SetUniformVec4 { shader: usize, name: String, value: [f32; 4] },
+    SetUniformMat4 { shader: usize, name: String, value: [f32; 16] },
+    SetFontTexture { texture: usize },
+    UseTexture { texture: usize },
+    Clear { color: [f32; 4] },
+    DrawMesh { vertex_count: usize, index_count: usize },
+    DrawMeshBuffer { mesh: usize },
+    DrawText { text: String, x: f32, y: f32, width: f32, height: f32, color: Color },
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_number(value: f32) -> String {
+    if value.is_finite() { format!("{}", value) } else { "null".into() }
+}
+
+fn json_array(values: &[f32]) -> String {
+    let values: Vec<String> = values.iter().map(|v| json_number(*v)).collect();
+    format!("[{}]", values.join(","))
+}
+
+impl Command {
+    pub fn to_json(&self) -> String {
+        match self {
+            Command::NewShader { shader } =>
+                format!("{{\"command\":\"new_shader\",\"shader\":{}}}", shader),
+            Command::NewImageTexture { texture, name } =>
+                format!("{{\"command\":\"new_image_texture\",\"texture\":{},\"name\":{}}}", texture, json_string(name)),
+            Command::NewDataTexture { texture, width, height } =>
+                format!("{{\"command\":\"new_data_texture\",\"texture\":{},\"width\":{},\"height\":{}}}", texture, width, height),
+            Command::LoadAsset { name } =>
+                format!("{{\"command\":\"load_asset\",\"name\":{}}}", json_string(name)),
+            Command::UploadMesh { mesh, vertex_count, index_count } =>
+                format!("{{\"command\":\"upload_mesh\",\"mesh\":{},\"vertex_count\":{},\"index_count\":{}}}", mesh, vertex_count, index_count),
+            Command::UseShader { shader } =>
+                format!("{{\"command\":\"use_shader\",\"shader\":{}}}", shader),
+            Command::SetUniformVec4 { shader, name, value } =>
+                format!("{{\"command\":\"set_uniform_vec4\",\"shader\":{},\"name\":{},\"value\":{}}}", shader, json_string(name), json_array(value)),
+            Command::SetUniformMat4 { shader, name, value } =>
+                format!("{{\"command\":\"set_uniform_mat4\",\"shader\":{},\"name\":{},\"value\":{}}}", shader, json_string(name), json_array(value)),
+            Command::SetFontTexture { texture } =>
+                format!("{{\"command\":\"set_font_texture\",\"texture\":{}}}", texture),
+            Command::UseTexture { texture } =>
+                format!("{{\"command\":\"use_texture\",\"texture\":{}}}", texture),
+            Command::Clear { color } =>
+                format!("{{\"command\":\"clear\",\"color\":{}}}", json_array(color)),
+            Command::DrawMesh { vertex_count, index_count } =>
+                format!("{{\"command\":\"draw_mesh\",\"vertex_count\":{},\"index_count\":{}}}", vertex_count, index_count),
+            Command::DrawMeshBuffer { mesh } =>
+                format!("{{\"command\":\"draw_mesh_buffer\",\"mesh\":{}}}", mesh),
+            Command::DrawText { text, x, y, width, height, color } =>
+                format!(
+                    "{{\"command\":\"draw_text\",\"text\":{},\"x\":{},\"y\":{},\"width\":{},\"height\":{},\"color\":[{},{},{},{}]}}",
+                    json_string(text), json_number(*x), json_number(*y), json_number(*width), json_number(*height),
+                    color.r, color.g, color.b, color.a
+                ),
+        }
+    }
+}
+
+pub struct RecordingContext {
+    width: usize,
+    height: usize,
+    next_id: Cell<usize>,
+    commands: RefCell<Vec<Command>>,
+}
+
+impl RecordingContext {
+    pub fn commands(&self) -> Ref<'_, Vec<Command>> {
+        self.commands.borrow()
+    }
+
+    // Returns the log so far and starts a new one, e.g. once per frame.
+    pub fn take_commands(&self) -> Vec<Command> {
+        self.commands.take()
+    }
+
+    pub fn to_json(&self) -> String {
+        let commands: Vec<String> = self.commands.borrow().iter().map(Command::to_json).collect();
+        format!("[{}]", commands.join(","))
+    }
+
+    fn record(&self, command: Command) {
+        self.commands.borrow_mut().push(command);
+    }
+
+    fn new_id(&self) -> usize {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        id
+    }
+}
+
+impl ContextType for RecordingContext {
+    type Shader = RecordingShader;
+    type Texture = RecordingTexture;
+    type MeshBuffer = RecordingMeshBuffer;
+
+    fn new(_title: &str, width: usize, height: usize) -> RecordingContext {
+        RecordingContext {
+            width,
+            height,
+            next_id: Cell::new(0),
+            commands: RefCell::new(vec![]),
+        }
+    }
+
+    fn new_shader(&self, _vert_src: &str, _frag_src: &str) -> Self::Shader {
+        let shader = self.new_id();
+        self.record(Command::NewShader { shader });
+        RecordingShader { id: shader }
+    }
+
+    async fn new_image_texture(&self, name: &str) -> Self::Texture {
+        let texture = self.new_id();
+        self.record(Command::NewImageTexture { texture, name: name.into() });
+        RecordingTexture { id: texture }
+    }
+
+    fn new_data_texture(&self, width: i32, height: i32, _data: Vec<u8>) -> Self::Texture {
+        let texture = self.new_id();
+        self.record(Command::NewDataTexture { texture, width, height });
+        RecordingTexture { id: texture }
+    }
+
+    // Assets still come from disk, since what is drawn depends on them.
+    async fn load_asset(&self, name: &str) -> Result<Vec<u8>, String> {
+        self.record(Command::LoadAsset { name: name.into() });
+        assets::load(name)
+    }
+
+    fn upload_mesh(&self, mesh: &Mesh) -> Self::MeshBuffer {
+        let id = self.new_id();
+        self.record(Command::UploadMesh { mesh: id, vertex_count: mesh.vertex_count(), index_count: mesh.indices.len() });
+        RecordingMeshBuffer { id }
+    }
+
+    fn use_shader(&self, shader: &Self::Shader) {
+        self.record(Command::UseShader { shader: shader.id });
+    }
+
+    fn set_uniform_vec4(&self, shader: &Self::Shader, uniform_name: &str, value: &Vec4) {
+        self.record(Command::SetUniformVec4 { shader: shader.id, name: uniform_name.into(), value: value.data });
+    }
+
+    fn set_uniform_mat4(&self, shader: &Self::Shader, uniform_name: &str, value: &Mat4) {
+        self.record(Command::SetUniformMat4 { shader: shader.id, name: uniform_name.into(), value: value.data });
+    }
+
+    fn set_font_texture(&mut self, texture: Self::Texture) {
+        self.record(Command::SetFontTexture { texture: texture.id });
+    }
+
+    fn use_texture(&self, texture: &Self::Texture) {
+        self.record(Command::UseTexture { texture: texture.id });
+    }
+
+    fn clear(&self, r: f32, g: f32, b: f32, a: f32) {
+        self.record(Command::Clear { color: [r, g, b, a] });
+    }
+
+    fn draw_mesh(&self, mesh: &Mesh) {
+        self.record(Command::DrawMesh { vertex_count: mesh.vertex_count(), index_count: mesh.indices.len() });
+    }
+
+    fn draw_mesh_buffer(&self, mesh: &Self::MeshBuffer) {
+        self.record(Command::DrawMeshBuffer { mesh: mesh.id });
+    }
+
+    fn draw_text(&self, text: &str, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.record(Command::DrawText { text: text.into(), x, y, width, height, color });
+    }
+
+    fn display_width(&self) -> i32 {
+        self.width as i32
+    }
+
+    fn display_height(&self) -> i32 {
+        self.height as i32
+    }
+
+    fn log(text: &str) {
+        eprintln!("{}", text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_json_strings() {
+        let command = Command::LoadAsset { name: "a \"b\"\\c\n\u{1}".into() };
+        assert_eq!(command.to_json(), r#"{"command":"load_asset","name":"a \"b\"\\c\n\u0001"}"#);
+    }
+
+    #[test]
+    fn writes_non_finite_numbers_as_null() {
+        let command = Command::Clear { color: [0.5, f32::NAN, f32::INFINITY, -1.0] };
+        assert_eq!(command.to_json(), r#"{"command":"clear","color":[0.5,null,null,-1]}"#);
+
+        let command = Command::DrawText {
+            text: "0".into(), x: -1.0, y: f32::NEG_INFINITY, width: 0.05, height: 0.2,
+            color: Color { r: 0, g: 255, b: 0, a: 255 },
+        };
+        assert_eq!(
+            command.to_json(),
+            r#"{"command":"draw_text","text":"0","x":-1,"y":null,"width":0.05,"height":0.2,"color":[0,255,0,255]}"#
+        );
+    }
+
+    #[test]
+    fn context_json_is_an_array_of_commands() {
+        let context = RecordingContext::new("", 4, 4);
+        assert_eq!(context.to_json(), "[]");
+
+        let shader = context.new_shader("", "");
+        context.use_shader(&shader);
+        assert_eq!(context.to_json(), r#"[{"command":"new_shader","shader":0},{"command":"use_shader","shader":0}]"#);
+    }
+}
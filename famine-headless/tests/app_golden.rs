@@ -0,0 +1,12 @@
+// Renders the demo application headlessly and compares it against the
+// committed golden image. Set FAMINE_UPDATE_GOLDEN=1 to regenerate it.
+
+use famine::assets;
+use famine_application::App;
+use famine_headless::{golden::assert_golden, HeadlessContext};
+
+#[test]
+fn app_matches_golden() {
+    assets::set_asset_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../assets"));
+    assert_golden::<App<HeadlessContext>>(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/app.png"), 30, 2);
+}
@@ -0,0 +1,32 @@
+// Checks the calls the demo application makes each frame.
+
+use famine::{executor::block_on, linalg::Mat4, time::FrameClock, Application, Color};
+use famine_application::App;
+use famine_headless::{golden::FRAME_TIME, recording::{Command, RecordingContext}};
+
+#[test]
+fn app_draws_its_rotation_in_green() {
+    let mut app = block_on(App::<RecordingContext>::new());
+    app.get_window().take_commands();
+
+    let mut clock = FrameClock::new();
+    for i in 0..3 {
+        block_on(app.update(clock.tick(i as f64 * FRAME_TIME)));
+        let commands = app.get_window().take_commands();
+
+        assert!(commands.iter().any(|command| matches!(
+            command,
+            Command::DrawText { x: -1.0, y: -1.0, color: Color { r: 0, g: 255, b: 0, a: 255 }, .. }
+        )), "{:?}", commands);
+
+        let vmp = commands.iter().find_map(|command| match command {
+            Command::SetUniformMat4 { name, value, .. } if name == "u_ViewModelProjection" => Some(*value),
+            _ => None,
+        });
+        let vmp = vmp.unwrap_or_else(|| panic!("No u_ViewModelProjection in {:?}", commands));
+        if i == 0 {
+            // Nothing has turned yet, leaving only the 640x480 aspect correction.
+            assert_eq!(vmp, Mat4::scale(0.75, 1.0, 1.0).data);
+        }
+    }
+}
@@ -1,8 +1,7 @@
-import init, {web_startup, web_update} from "./pkg/famine_web.js"
+import init, {WebApplication} from "./pkg/famine_web.js"
 
-let application
-init().then(() => {
-    web_startup().then(
-        app => setInterval(web_update, 33, app)
-    )
+init().then(() => WebApplication.start()).then(app => {
+    window.addEventListener("pagehide", event => {
+        if (!event.persisted) app.shutdown()
+    })
 })
\ No newline at end of file
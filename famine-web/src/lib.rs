@@ -1,11 +1,11 @@
-use std::{alloc::{alloc, dealloc, Layout}, ptr, sync::Arc};
+use std::{cell::{Cell, RefCell}, rc::Rc};
 
 
 use famine_application::App;
 use wasm_bindgen::prelude::*;
-use wasm_bindgen_futures::JsFuture;
-use web_sys::{console, js_sys, HtmlCanvasElement, HtmlImageElement, WebGl2RenderingContext, WebGlProgram, WebGlShader, WebGlTexture};
-use famine::{shaders::font_shader::{FONT_FRAG_SHADER, FONT_VERT_SHADER}, Application, ContextType, Mesh};
+use wasm_bindgen_futures::{future_to_promise, spawn_local, JsFuture};
+use web_sys::{console, js_sys, HtmlCanvasElement, HtmlImageElement, Response, WebGl2RenderingContext, WebGlBuffer, WebGlProgram, WebGlShader, WebGlTexture, WebGlVertexArrayObject};
+use famine::{shaders::font_shader::{FONT_FRAG_SHADER, FONT_VERT_SHADER}, time::FrameClock, vertex::VertexLayout, Application, ContextType, Mesh};
 use famine::linalg::Mat4;
 
 #[wasm_bindgen]
@@ -13,22 +13,51 @@ pub struct WebContext {
     gl: web_sys::WebGl2RenderingContext,
     font_texture: Option<WebTexture>,
     font_shader: Option<WebShader>,
+    current_program: RefCell<Option<WebGlProgram>>,
+    // Reused by draw_mesh for meshes that only live for one draw.
+    stream_mesh: WebMeshBuffer,
 }
 
 pub struct WebShader {
+    gl: WebGl2RenderingContext,
     program: WebGlProgram,
 }
 
+impl Drop for WebShader {
+    fn drop(&mut self) {
+        self.gl.delete_program(Some(&self.program));
+    }
+}
+
+pub struct WebMeshBuffer {
+    gl: WebGl2RenderingContext,
+    vao: WebGlVertexArrayObject,
+    vertex_buffer: WebGlBuffer,
+    index_buffer: WebGlBuffer,
+    count: Cell<i32>,
+    indexed: Cell<bool>,
+    layout: RefCell<VertexLayout>,
+    // The program the attribute pointers were last set up for.
+    program: RefCell<Option<WebGlProgram>>,
+}
+
 pub enum TextureData {
     ImageData(HtmlImageElement),
     RawData(i32, i32, Vec<u8>),
 }
 
 pub struct WebTexture {
+    gl: WebGl2RenderingContext,
     pub data: TextureData,
     pub gl_texture: WebGlTexture,
 }
 
+impl Drop for WebTexture {
+    fn drop(&mut self) {
+        self.gl.delete_texture(Some(&self.gl_texture));
+    }
+}
+
 fn compile_shader(context: &WebGl2RenderingContext, shader_type: u32, source: &str) -> WebGlShader {
     let shader = context
         .create_shader(shader_type)
@@ -72,9 +101,120 @@ pub fn link_program(context: &WebGl2RenderingContext, vert_shader: &WebGlShader,
     program
 }
 
+fn create_mesh_buffer(gl: &WebGl2RenderingContext) -> WebMeshBuffer {
+    let (Some(vao), Some(vertex_buffer), Some(index_buffer)) = (gl.create_vertex_array(), gl.create_buffer(), gl.create_buffer()) else {
+        console::log_1(&"Failed to create mesh buffers".into());
+        panic!()
+    };
+
+    // The element array binding is part of the vao.
+    gl.bind_vertex_array(Some(&vao));
+    gl.bind_buffer(WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER, Some(&index_buffer));
+
+    WebMeshBuffer {
+        gl: gl.clone(),
+        vao,
+        vertex_buffer,
+        index_buffer,
+        count: Cell::new(0),
+        indexed: Cell::new(false),
+        layout: RefCell::new(VertexLayout::default()),
+        program: RefCell::new(None),
+    }
+}
+
+impl Drop for WebMeshBuffer {
+    fn drop(&mut self) {
+        self.gl.delete_vertex_array(Some(&self.vao));
+        self.gl.delete_buffer(Some(&self.vertex_buffer));
+        self.gl.delete_buffer(Some(&self.index_buffer));
+    }
+}
+
+impl WebContext {
+    fn fill_mesh_buffer(&self, buffer: &WebMeshBuffer, mesh: &Mesh, usage: u32) {
+        self.gl.bind_vertex_array(Some(&buffer.vao));
+
+        let vertices = unsafe { js_sys::Float32Array::view(mesh.vertices.as_slice()) };
+        self.gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer.vertex_buffer));
+        self.gl.buffer_data_with_array_buffer_view(WebGl2RenderingContext::ARRAY_BUFFER, &vertices, usage);
+
+        if mesh.is_indexed() {
+            let indices = unsafe { js_sys::Uint32Array::view(mesh.indices.as_slice()) };
+            self.gl.buffer_data_with_array_buffer_view(WebGl2RenderingContext::ELEMENT_ARRAY_BUFFER, &indices, usage);
+        }
+
+        buffer.count.set(mesh.element_count() as i32);
+        buffer.indexed.set(mesh.is_indexed());
+        if *buffer.layout.borrow() != mesh.layout {
+            *buffer.layout.borrow_mut() = mesh.layout.clone();
+            buffer.program.borrow_mut().take();
+        }
+    }
+
+    // Names of the attributes the program reads, without the gl_ built ins.
+    fn program_attributes(&self, program: &WebGlProgram) -> Vec<String> {
+        let count = self.gl.get_program_parameter(program, WebGl2RenderingContext::ACTIVE_ATTRIBUTES)
+            .as_f64().unwrap_or(0.0) as u32;
+        (0..count)
+            .filter_map(|i| self.gl.get_active_attrib(program, i))
+            .map(|info| info.name())
+            .filter(|name| !name.starts_with("gl_"))
+            .collect()
+    }
+
+    fn bind_attributes(&self, buffer: &WebMeshBuffer, program: &WebGlProgram) {
+        self.gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer.vertex_buffer));
+
+        let layout = buffer.layout.borrow();
+        let shader_attributes = self.program_attributes(program);
+        if let Err(err) = layout.validate(shader_attributes.iter().map(String::as_str)) {
+            Self::log(&format!("Famine Warning: {}.", err));
+        }
+
+        for name in &shader_attributes {
+            let location = self.gl.get_attrib_location(program, name);
+            if location < 0 {
+                continue;
+            }
+            let location = location as u32;
+
+            match layout.find(name) {
+                Some((offset, attribute)) => {
+                    self.gl.vertex_attrib_pointer_with_i32(location, attribute.components as i32,
+                        attribute.kind.gl_enum(), attribute.normalized, layout.stride() as i32, offset as i32);
+                    self.gl.enable_vertex_attrib_array(location);
+                }
+                // Left over from a previous program or layout.
+                None => self.gl.disable_vertex_attrib_array(location),
+            }
+        }
+    }
+
+    fn draw_buffer(&self, buffer: &WebMeshBuffer) {
+        let Some(program) = self.current_program.borrow().clone() else {
+            Self::log("Famine Warning: Mesh drawn without a shader in use.");
+            return
+        };
+
+        self.gl.bind_vertex_array(Some(&buffer.vao));
+        if buffer.program.borrow().as_ref() != Some(&program) {
+            self.bind_attributes(buffer, &program);
+            *buffer.program.borrow_mut() = Some(program);
+        }
+
+        if buffer.indexed.get() {
+            self.gl.draw_elements_with_i32(WebGl2RenderingContext::TRIANGLES, buffer.count.get(), WebGl2RenderingContext::UNSIGNED_INT, 0);
+        } else {
+            self.gl.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, buffer.count.get());
+        }
+    }
+}
+
 impl ContextType for WebContext {
     type Shader = WebShader;
     type Texture = WebTexture;
+    type MeshBuffer = WebMeshBuffer;
 
     fn new(title: &str, _width: usize, _height: usize) -> WebContext {
         let window: web_sys::Window = web_sys::window().expect("Failed to get global window!");
@@ -91,20 +231,19 @@ impl ContextType for WebContext {
 
         document.set_title(title);
 
-        let buffer = match gl.create_buffer() {
-            Some(b) => b,
-            None => {
-                console::log_1(&"Failed to create buffer".into());
-                panic!()
-            },
-        };
-        gl.bind_buffer(WebGl2RenderingContext::ARRAY_BUFFER, Some(&buffer));
+        let stream_mesh = create_mesh_buffer(&gl);
 
         gl.enable(WebGl2RenderingContext::CULL_FACE);
         gl.enable(WebGl2RenderingContext::BLEND);
         gl.blend_func(WebGl2RenderingContext::SRC_ALPHA, WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA);
 
-        WebContext { gl, font_texture: None, font_shader: None }
+        WebContext {
+            gl,
+            font_texture: None,
+            font_shader: None,
+            current_program: RefCell::new(None),
+            stream_mesh,
+        }
     }
 
     fn clear(&self, r: f32, g: f32, b: f32, a: f32) {
@@ -113,9 +252,18 @@ impl ContextType for WebContext {
     }
 
     fn draw_mesh(&self, mesh: &Mesh) {
-        let data = unsafe { js_sys::Float32Array::view(&mesh.vertices.as_slice()) };
-        self.gl.buffer_data_with_array_buffer_view(WebGl2RenderingContext::ARRAY_BUFFER, &data, WebGl2RenderingContext::STATIC_DRAW);
-        self.gl.draw_arrays(WebGl2RenderingContext::TRIANGLES, 0, (mesh.vertices.len() / 5) as i32);
+        self.fill_mesh_buffer(&self.stream_mesh, mesh, WebGl2RenderingContext::STREAM_DRAW);
+        self.draw_buffer(&self.stream_mesh);
+    }
+
+    fn draw_mesh_buffer(&self, mesh: &Self::MeshBuffer) {
+        self.draw_buffer(mesh);
+    }
+
+    fn upload_mesh(&self, mesh: &Mesh) -> Self::MeshBuffer {
+        let buffer = create_mesh_buffer(&self.gl);
+        self.fill_mesh_buffer(&buffer, mesh, WebGl2RenderingContext::STATIC_DRAW);
+        buffer
     }
 
     fn new_shader(&self, vert_src: &str, frag_src: &str) -> Self::Shader {
@@ -124,29 +272,14 @@ impl ContextType for WebContext {
         let program = link_program(&self.gl, &vert_shader, &frag_shader);
 
         WebShader {
+            gl: self.gl.clone(),
             program,
         }
     }
 
     fn use_shader(&self, shader: &Self::Shader) {
         self.gl.use_program(Some(&shader.program));
-
-        let vao = match self.gl.create_vertex_array() {
-            Some(va) => va,
-            None => {
-                console::log_1(&"Failed to create vao".into());
-                panic!()
-            }
-        };
-        let position_attribute_location: u32 = self.gl.get_attrib_location(&shader.program, "v_position") as u32;
-        let uv_attribute_location: u32 = self.gl.get_attrib_location(&shader.program, "v_uv") as u32;
-        self.gl.bind_vertex_array(Some(&vao));
-        self.gl.vertex_attrib_pointer_with_i32(position_attribute_location, 3,
-             WebGl2RenderingContext::FLOAT, false, 20, 0);
-        self.gl.vertex_attrib_pointer_with_i32(uv_attribute_location, 2,
-            WebGl2RenderingContext::FLOAT, false, 20, 12);
-        self.gl.enable_vertex_attrib_array(position_attribute_location);
-        self.gl.enable_vertex_attrib_array(uv_attribute_location);
+        *self.current_program.borrow_mut() = Some(shader.program.clone());
     }
     
     fn set_uniform_vec4(&self, shader: &Self::Shader, uniform_name: &str, value: &famine::linalg::Vec4) {
@@ -195,6 +328,7 @@ impl ContextType for WebContext {
         self.gl.generate_mipmap(WebGl2RenderingContext::TEXTURE_2D);
 
         WebTexture {
+            gl: self.gl.clone(),
             data: TextureData::ImageData(image),
             gl_texture,
         }
@@ -219,11 +353,27 @@ impl ContextType for WebContext {
         self.gl.generate_mipmap(WebGl2RenderingContext::TEXTURE_2D);
 
         WebTexture {
+            gl: self.gl.clone(),
             data: TextureData::RawData(width, height, data),
             gl_texture,
         }
     }
 
+    async fn load_asset(&self, name: &str) -> Result<Vec<u8>, String> {
+        let url = format!("pkg/assets/{}", name);
+        let error = |err: JsValue| format!("Failed to fetch {}: {}", url, err.as_string().unwrap_or("Unknown Error".into()));
+
+        let window = web_sys::window().expect("Failed to get global window!");
+        let response: Response = JsFuture::from(window.fetch_with_str(&url)).await.map_err(error)?
+            .dyn_into().map_err(error)?;
+        if !response.ok() {
+            return Err(format!("Failed to fetch {}: HTTP {}", url, response.status()));
+        }
+
+        let buffer = JsFuture::from(response.array_buffer().map_err(error)?).await.map_err(error)?;
+        Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+    }
+
     fn use_texture(&self, texture: &Self::Texture) {
         self.gl.active_texture(WebGl2RenderingContext::TEXTURE0);
         self.gl.bind_texture(WebGl2RenderingContext::TEXTURE_2D, Some(&texture.gl_texture));
@@ -251,30 +401,7 @@ impl ContextType for WebContext {
         self.use_texture(self.font_texture.as_ref().unwrap());
         self.set_uniform_vec4(self.font_shader.as_ref().unwrap(), "u_color", &color.as_vec4());
 
-        let char_w: f32 = width / text.len() as f32;
-        let u_per_column: f32 = 1.0 / 16.0;
-        let v_per_row: f32 = 1.0 / 8.0;
-        for (i, c) in text.chars().enumerate() {
-
-            let row: f32 = ((c as u32 - '!' as u32) as f32 / 16.0).floor();
-            let column: f32 = ((c as u32 - '!' as u32) % 16) as f32;
-
-            let char_x: f32 = x + i as f32 * char_w;
-            let char_u1: f32 = u_per_column * column;
-            let char_u2: f32 = char_u1 + u_per_column;
-            let char_v1: f32 = v_per_row * (row + 1.0);
-            let char_v2: f32 = v_per_row * row;
-            let mesh = Mesh { vertices: vec![
-                char_x,          y + height, 0.0, char_u1, char_v2,
-                char_x,          y,          0.0, char_u1, char_v1,
-                char_x + char_w, y + height, 0.0, char_u2, char_v2,
-                char_x,          y,          0.0, char_u1, char_v1,
-                char_x + char_w, y,          0.0, char_u2, char_v1,
-                char_x + char_w, y + height, 0.0, char_u2, char_v2,
-            ]};
-            self.draw_mesh(&mesh);
-        }
-
+        self.draw_mesh(&Mesh::text(text, x, y, width, height));
     }
     
     fn set_font_texture(&mut self, texture: Self::Texture) {
@@ -287,29 +414,125 @@ impl ContextType for WebContext {
     }
 }
 
-#[wasm_bindgen]
-pub async fn web_startup() -> *mut App<WebContext> {
-    let layout = Layout::new::<App<WebContext>>();
-    let app_ptr = unsafe { alloc(layout) as *mut App<WebContext> };
+type FrameCallback = Closure<dyn FnMut(f64)>;
+
+struct ApplicationState {
+    // Taken out while an update is running.
+    app: RefCell<Option<App<WebContext>>>,
+    disposed: Cell<bool>,
+    clock: RefCell<FrameClock>,
+    frame_callback: RefCell<Option<FrameCallback>>,
+    frame_request: Cell<Option<i32>>,
+}
+
+impl ApplicationState {
+    async fn update(&self, timestamp: f64) -> Result<(), JsValue> {
+        if self.disposed.get() {
+            return Err("Famine Error: Application has been shut down.".into());
+        }
 
-    if app_ptr.is_null() {
-        console::log_1(&"Failed to allocate memory for the applicaiton.".into());
-        return ptr::null_mut();
+        let mut app = self.app.borrow_mut().take()
+            .ok_or_else(|| JsValue::from("Famine Error: Application is already updating."))?;
+        let frame = self.clock.borrow_mut().tick(timestamp / 1000.0);
+        app.update(frame).await;
+
+        // A shutdown during the update drops the application here instead.
+        if !self.disposed.get() {
+            *self.app.borrow_mut() = Some(app);
+        }
+        Ok(())
     }
-    
-    unsafe { ptr::write(app_ptr, App::<WebContext>::new().await) }
-    
-    console::log_1(&"Successful application startup.".into());
 
-    app_ptr
+    fn request_frame(&self) {
+        if let Some(callback) = self.frame_callback.borrow().as_ref() {
+            let request = web_sys::window().expect("Failed to get global window!")
+                .request_animation_frame(callback.as_ref().unchecked_ref())
+                .expect("Failed to request animation frame!");
+            self.frame_request.set(Some(request));
+        }
+    }
+
+    fn cancel_frame(&self) {
+        if let Some(request) = self.frame_request.take() {
+            if let Some(window) = web_sys::window() {
+                let _ = window.cancel_animation_frame(request);
+            }
+        }
+    }
+
+    fn shutdown(&self) {
+        self.disposed.set(true);
+        self.cancel_frame();
+        self.frame_callback.borrow_mut().take();
+        self.app.borrow_mut().take();
+    }
+}
+
+impl Drop for ApplicationState {
+    fn drop(&mut self) {
+        self.cancel_frame();
+    }
+}
+
+// Runs one update per animation frame. The next frame is only requested once
+// the previous update has completed, so updates never overlap.
+fn start_frame_loop(state: &Rc<ApplicationState>) {
+    let weak = Rc::downgrade(state);
+    *state.frame_callback.borrow_mut() = Some(Closure::new(move |timestamp: f64| {
+        let Some(state) = weak.upgrade() else { return };
+        state.frame_request.set(None);
+        spawn_local(async move {
+            let _ = state.update(timestamp).await;
+            if !state.disposed.get() {
+                state.request_frame();
+            }
+        });
+    }));
+
+    state.request_frame();
 }
 
 #[wasm_bindgen]
-pub async fn web_update(application: *mut App<WebContext>) {
-    unsafe { &mut *application }.update().await;
+pub struct WebApplication {
+    state: Rc<ApplicationState>,
 }
 
 #[wasm_bindgen]
-pub fn web_shutdown(application: *mut App<WebContext>) {
-    unsafe { dealloc(application as *mut u8, Layout::new::<App<WebContext>>()) }
-}
\ No newline at end of file
+impl WebApplication {
+    // Creates the application and starts its frame loop.
+    pub async fn start() -> WebApplication {
+        let app = App::<WebContext>::new().await;
+        console::log_1(&"Successful application startup.".into());
+
+        let state = Rc::new(ApplicationState {
+            app: RefCell::new(Some(app)),
+            disposed: Cell::new(false),
+            clock: RefCell::new(FrameClock::new()),
+            frame_callback: RefCell::new(None),
+            frame_request: Cell::new(None),
+        });
+        start_frame_loop(&state);
+
+        WebApplication { state }
+    }
+
+    // Runs a single update with a timestamp in milliseconds. The promise
+    // rejects once the application has been shut down.
+    pub fn update(&self, timestamp: f64) -> js_sys::Promise {
+        let state = self.state.clone();
+        future_to_promise(async move {
+            state.update(timestamp).await?;
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    // Stops the frame loop and drops the application.
+    pub fn shutdown(&self) {
+        self.state.shutdown();
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn disposed(&self) -> bool {
+        self.state.disposed.get()
+    }
+}
@@ -0,0 +1,92 @@
+// Batch matrix benchmarks, run with
+//     cargo bench -p famine --bench linalg
+
+use std::{hint::black_box, time::Instant};
+
+use famine::linalg::{Mat4, Vec3, Vec4};
+
+const OBJECTS: usize = 5_000;
+const ROUNDS: u32 = 50;
+const SAMPLES: u32 = 20;
+
+// The matrix product as it was before the kernels, for a fixed baseline.
+fn get_set_mul(a: &Mat4, b: &Mat4) -> Mat4 {
+    let mut res = Mat4::zero();
+    for i in 0..4 {
+        for j in 0..4 {
+            res.set(i, j, a.get(0, j) * b.get(i, 0)
+                + a.get(1, j) * b.get(i, 1)
+                + a.get(2, j) * b.get(i, 2)
+                + a.get(3, j) * b.get(i, 3)
+            );
+        }
+    }
+    res
+}
+
+// Reports the fastest of several samples, which is the least disturbed by
+// whatever else the machine is doing.
+fn bench(name: &str, mut f: impl FnMut()) {
+    f();
+    let mut best = f64::MAX;
+    for _ in 0..SAMPLES {
+        let start = Instant::now();
+        for _ in 0..ROUNDS {
+            f();
+        }
+        best = best.min(start.elapsed().as_nanos() as f64 / (ROUNDS as usize * OBJECTS) as f64);
+    }
+    println!("{:<28} {:>8.2} ns/object", name, best);
+}
+
+fn main() {
+    let view_projection = Mat4::perspective(1.0, 16.0 / 9.0, 0.1, 100.0)
+        * Mat4::look_at(&Vec3::new([0.0, 2.0, 5.0]), &Vec3::ZERO, &Vec3::new([0.0, 1.0, 0.0]));
+    let models: Vec<Mat4> = (0..OBJECTS)
+        .map(|i| {
+            let t = i as f32 * 0.01;
+            Mat4::translate(t.sin(), t.cos(), -t) * Mat4::rotate_y(t)
+        })
+        .collect();
+    let mut out = vec![Mat4::zero(); OBJECTS];
+
+    println!("{} objects", OBJECTS);
+
+    bench("get/set mul (baseline)", || {
+        for (model, out) in models.iter().zip(out.iter_mut()) {
+            *out = get_set_mul(black_box(model), &view_projection);
+        }
+        black_box(&out);
+    });
+
+    bench("*", || {
+        for (model, out) in models.iter().zip(out.iter_mut()) {
+            *out = view_projection * *black_box(model);
+        }
+        black_box(&out);
+    });
+
+    bench("mul_into", || {
+        for (model, out) in models.iter().zip(out.iter_mut()) {
+            view_projection.mul_into(black_box(model), out);
+        }
+        black_box(&out);
+    });
+
+    bench("*=", || {
+        for (model, out) in models.iter().zip(out.iter_mut()) {
+            *out = view_projection;
+            *out *= black_box(model);
+        }
+        black_box(&out);
+    });
+
+    let point = Vec4::new([0.5, -0.25, 1.0, 1.0]);
+    let mut points = vec![Vec4::ZERO; OBJECTS];
+    bench("mat4 * vec4", || {
+        for (mvp, out) in out.iter().zip(points.iter_mut()) {
+            *out = black_box(mvp) * point;
+        }
+        black_box(&points);
+    });
+}
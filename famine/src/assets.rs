@@ -0,0 +1,34 @@
+// Asset loading for native backends. Names are resolved against the asset
+// directory, which is the one given to `set_asset_dir`, otherwise the
+// FAMINE_ASSET_DIR environment variable, otherwise "assets" in the working
+// directory. The web backend serves the same names from "pkg/assets/".
+
+use std::{path::PathBuf, sync::RwLock};
+
+use crate::image::{self, Image};
+
+static ASSET_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+pub fn set_asset_dir(path: impl Into<PathBuf>) {
+    *ASSET_DIR.write().unwrap() = Some(path.into());
+}
+
+pub fn asset_dir() -> PathBuf {
+    if let Some(dir) = ASSET_DIR.read().unwrap().as_ref() {
+        return dir.clone();
+    }
+    std::env::var_os("FAMINE_ASSET_DIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("assets"))
+}
+
+pub fn asset_path(name: &str) -> PathBuf {
+    asset_dir().join(name)
+}
+
+pub fn load(name: &str) -> Result<Vec<u8>, String> {
+    let path = asset_path(name);
+    std::fs::read(&path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))
+}
+
+pub fn load_image(name: &str) -> Result<Image, String> {
+    image::decode(&load(name)?).map_err(|err| format!("Failed to decode {}: {}", name, err))
+}
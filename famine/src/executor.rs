@@ -0,0 +1,45 @@
+// Drives `Application` futures on native targets, where there is no
+// browser event loop to hand them to.
+
+use std::{future::Future, pin::pin, sync::Arc, task::{Context, Poll, Wake, Waker}, thread::{self, Thread}, time::Instant};
+
+use crate::{time::FrameClock, Application, ContextType};
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Polls the future on the current thread, parking between wakeups.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+// Starts the application and completes one `update` per frame. After each
+// frame `end_frame` presents it and returns whether to keep running.
+pub fn run<Context, App>(mut end_frame: impl FnMut(&Context) -> bool) -> App
+where
+    Context: ContextType,
+    App: Application<Context>,
+{
+    let mut app = block_on(App::new());
+    let start = Instant::now();
+    let mut clock = FrameClock::new();
+    loop {
+        block_on(app.update(clock.tick(start.elapsed().as_secs_f64())));
+        if !end_frame(app.get_window()) {
+            return app;
+        }
+    }
+}
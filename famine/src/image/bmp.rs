@@ -0,0 +1,168 @@
+use super::{check_dimensions, Image};
+
+fn read_u16(bytes: &[u8], at: usize) -> Result<u16, String> {
+    let b = bytes.get(at..at + 2).ok_or("BMP header too short")?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Result<u32, String> {
+    let b = bytes.get(at..at + 4).ok_or("BMP header too short")?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+// Extracts a channel described by a bitfield mask, scaled to 8 bits.
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let shift = mask.trailing_zeros();
+    let max = mask >> shift;
+    (((value & mask) >> shift) as u64 * 255 / max as u64) as u8
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    if bytes.len() < 26 || &bytes[..2] != b"BM" {
+        return Err("Not a BMP file".into());
+    }
+
+    let data_offset = read_u32(bytes, 10)? as usize;
+    let header_size = read_u32(bytes, 14)? as usize;
+    if header_size < 40 {
+        return Err(format!("Unsupported BMP header size {}", header_size));
+    }
+
+    let width = read_u32(bytes, 18)? as i32;
+    let height = read_u32(bytes, 22)? as i32;
+    let depth = read_u16(bytes, 28)?;
+    let compression = read_u32(bytes, 30)?;
+    let colors_used = read_u32(bytes, 46)? as usize;
+
+    if width <= 0 || height == 0 {
+        return Err("Invalid BMP dimensions".into());
+    }
+    let top_down = height < 0;
+    let width = width as usize;
+    let height = height.unsigned_abs() as usize;
+    check_dimensions("BMP", width, height)?;
+
+    // Masks live in the header for V4/V5 headers and directly after it otherwise.
+    let (red_mask, green_mask, blue_mask, alpha_mask) = match (compression, depth) {
+        (3 | 6, 16 | 32) => {
+            let at = if header_size >= 52 { 54 } else { 14 + header_size };
+            let alpha = if header_size >= 56 || compression == 6 { read_u32(bytes, at + 12)? } else { 0 };
+            (read_u32(bytes, at)?, read_u32(bytes, at + 4)?, read_u32(bytes, at + 8)?, alpha)
+        }
+        (0, 16) => (0x7c00, 0x03e0, 0x001f, 0),
+        (0, 32) => (0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0),
+        (0, 1 | 4 | 8 | 24) => (0, 0, 0, 0),
+        _ => return Err(format!("Unsupported BMP compression {} with bit depth {}", compression, depth)),
+    };
+
+    let mut palette: Vec<[u8; 4]> = vec![];
+    if depth <= 8 {
+        let count = if colors_used == 0 { 1 << depth } else { colors_used };
+        let start = 14 + header_size;
+        let table = count.checked_mul(4)
+            .and_then(|size| bytes.get(start..start.checked_add(size)?))
+            .ok_or("BMP color table runs past the end of the file")?;
+        palette = table.chunks_exact(4).map(|c| [c[2], c[1], c[0], 255]).collect();
+    }
+
+    let stride = (width * depth as usize).div_ceil(32) * 4;
+    let pixels = bytes.get(data_offset..)
+        .and_then(|data| data.get(..stride * height))
+        .ok_or("BMP pixel data too short")?;
+
+    let mut image = Image::new(width, height);
+    for (row, line) in pixels.chunks_exact(stride).enumerate() {
+        let y = if top_down { row } else { height - 1 - row };
+
+        for x in 0..width {
+            let rgba = match depth {
+                1 | 4 | 8 => {
+                    let bit = x * depth as usize;
+                    let shift = 8 - depth as usize - bit % 8;
+                    let index = ((line[bit / 8] >> shift) & ((1u16 << depth) - 1) as u8) as usize;
+                    *palette.get(index).ok_or("BMP color index past the end of the color table")?
+                }
+                24 => [line[3 * x + 2], line[3 * x + 1], line[3 * x], 255],
+                _ => {
+                    let value = if depth == 16 {
+                        u16::from_le_bytes([line[2 * x], line[2 * x + 1]]) as u32
+                    } else {
+                        u32::from_le_bytes([line[4 * x], line[4 * x + 1], line[4 * x + 2], line[4 * x + 3]])
+                    };
+                    let alpha = if alpha_mask == 0 { 255 } else { channel(value, alpha_mask) };
+                    [channel(value, red_mask), channel(value, green_mask), channel(value, blue_mask), alpha]
+                }
+            };
+            image.set_pixel(x, y, rgba);
+        }
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A BITMAPINFOHEADER file with uncompressed pixel data.
+    fn bmp(width: i32, height: i32, depth: u16, pixels: &[u8]) -> Vec<u8> {
+        let mut out = b"BM".to_vec();
+        out.extend_from_slice(&(54 + pixels.len() as u32).to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&54u32.to_le_bytes());
+        out.extend_from_slice(&40u32.to_le_bytes());
+        out.extend_from_slice(&width.to_le_bytes());
+        out.extend_from_slice(&height.to_le_bytes());
+        out.extend_from_slice(&1u16.to_le_bytes());
+        out.extend_from_slice(&depth.to_le_bytes());
+        out.extend_from_slice(&[0; 24]);
+        out.extend_from_slice(pixels);
+        out
+    }
+
+    // Two 24 bit rows of two BGR pixels, each padded to 8 bytes.
+    const ROWS: [u8; 16] = [
+        0, 0, 255, 0, 255, 0, 0, 0,
+        255, 0, 0, 255, 255, 255, 0, 0,
+    ];
+
+    #[test]
+    fn rows_are_bottom_up_unless_height_is_negative() {
+        let image = decode(&bmp(2, 2, 24, &ROWS)).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.pixel(0, 1), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(1, 1), [0, 255, 0, 255]);
+        assert_eq!(image.pixel(0, 0), [0, 0, 255, 255]);
+        assert_eq!(image.pixel(1, 0), [255, 255, 255, 255]);
+
+        let flipped = decode(&bmp(2, -2, 24, &ROWS)).unwrap();
+        assert_eq!(flipped.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(flipped.pixel(1, 1), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn decodes_32_bit_pixels() {
+        let image = decode(&bmp(1, 1, 32, &[10, 20, 30, 0])).unwrap();
+        assert_eq!(image.pixel(0, 0), [30, 20, 10, 255]);
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        assert!(decode(&bmp(2, 2, 24, &ROWS[..12])).is_err());
+        assert!(decode(&bmp(2, 2, 24, &ROWS)[..30]).is_err());
+        assert!(decode(&bmp(2, 2, 8, &ROWS)).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_headers() {
+        assert!(decode(&bmp(1 << 30, 1 << 30, 32, &[])).is_err());
+        assert!(decode(&bmp(i32::MAX, i32::MIN, 24, &ROWS)).is_err());
+
+        let mut far_offset = bmp(2, 2, 24, &ROWS);
+        far_offset[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(decode(&far_offset).is_err());
+    }
+}
@@ -0,0 +1,433 @@
+// zlib / DEFLATE (RFC 1950, RFC 1951) decompression, and compression with
+// LZ77 matches and the fixed Huffman codes.
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader { data, pos: 0, bit: 0 }
+    }
+
+    fn bits(&mut self, count: u32) -> Result<u32, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = *self.data.get(self.pos).ok_or("Unexpected end of deflate stream")?;
+            value |= (((byte >> self.bit) & 1) as u32) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(value)
+    }
+
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+// Canonical Huffman code, decoded one bit at a time.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let mut offsets = [0u16; 16];
+        for i in 1..16 {
+            offsets[i] = offsets[i - 1] + counts[i - 1];
+        }
+
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u16, String> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..16 {
+            code |= reader.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("Invalid Huffman code".into())
+    }
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    lengths[..144].fill(8);
+    lengths[144..256].fill(9);
+    lengths[256..280].fill(7);
+    lengths[280..].fill(8);
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_tables(reader: &mut BitReader) -> Result<(Huffman, Huffman), String> {
+    let literal_count = reader.bits(5)? as usize + 257;
+    let distance_count = reader.bits(5)? as usize + 1;
+    let code_count = reader.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for &i in CODE_LENGTH_ORDER.iter().take(code_count) {
+        code_lengths[i] = reader.bits(3)? as u8;
+    }
+    let code_huffman = Huffman::new(&code_lengths);
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_huffman.decode(reader)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => {
+                let previous = *lengths[..i].last().ok_or("Repeated code length with no previous length")?;
+                (previous, 3 + reader.bits(2)? as usize)
+            }
+            17 => (0, 3 + reader.bits(3)? as usize),
+            _ => (0, 11 + reader.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() {
+            return Err("Too many code lengths".into());
+        }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+
+    Ok((Huffman::new(&lengths[..literal_count]), Huffman::new(&lengths[literal_count..])))
+}
+
+fn inflate_block(reader: &mut BitReader, out: &mut Vec<u8>, literals: &Huffman, distances: &Huffman) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(reader)? as usize;
+        match symbol {
+            0..=255 => out.push(symbol as u8),
+            256 => return Ok(()),
+            _ => {
+                let symbol = symbol - 257;
+                if symbol >= 29 {
+                    return Err("Invalid length symbol".into());
+                }
+                let length = LENGTH_BASE[symbol] as usize + reader.bits(LENGTH_EXTRA[symbol] as u32)? as usize;
+
+                let symbol = distances.decode(reader)? as usize;
+                if symbol >= 30 {
+                    return Err("Invalid distance symbol".into());
+                }
+                let distance = DIST_BASE[symbol] as usize + reader.bits(DIST_EXTRA[symbol] as u32)? as usize;
+                if distance > out.len() {
+                    return Err("Distance too far back".into());
+                }
+
+                let start = out.len() - distance;
+                for i in 0..length {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
+
+pub fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut reader = BitReader::new(data);
+    let mut out = vec![];
+
+    loop {
+        let last = reader.bits(1)? == 1;
+        match reader.bits(2)? {
+            0 => {
+                reader.align();
+                let header = data.get(reader.pos..reader.pos + 4).ok_or("Unexpected end of deflate stream")?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                let inverse = u16::from_le_bytes([header[2], header[3]]) as usize;
+                if length != !inverse & 0xffff {
+                    return Err("Corrupt stored block length".into());
+                }
+                reader.pos += 4;
+                out.extend_from_slice(data.get(reader.pos..reader.pos + length).ok_or("Unexpected end of deflate stream")?);
+                reader.pos += length;
+            }
+            1 => {
+                let (literals, distances) = fixed_tables();
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_tables(&mut reader)?;
+                inflate_block(&mut reader, &mut out, &literals, &distances)?;
+            }
+            _ => return Err("Invalid deflate block type".into()),
+        }
+
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+pub fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+pub fn zlib_decompress(data: &[u8]) -> Result<Vec<u8>, String> {
+    if data.len() < 6 {
+        return Err("zlib stream too short".into());
+    }
+    if data[0] & 0x0f != 8 || !(((data[0] as u16) << 8) | data[1] as u16).is_multiple_of(31) {
+        return Err("Invalid zlib header".into());
+    }
+    if data[1] & 0x20 != 0 {
+        return Err("zlib preset dictionaries are not supported".into());
+    }
+
+    let out = inflate(&data[2..])?;
+    let checksum = u32::from_be_bytes(data[data.len() - 4..].try_into().unwrap());
+    if checksum != adler32(&out) {
+        return Err("zlib checksum mismatch".into());
+    }
+    Ok(out)
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn bits(&mut self, value: u32, count: u32) {
+        self.buffer |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes are packed starting from their most significant bit.
+    fn code(&mut self, code: u32, length: u32) {
+        self.bits(code.reverse_bits() >> (32 - length), length);
+    }
+
+    fn literal(&mut self, symbol: u32) {
+        match symbol {
+            0..=143 => self.code(0x30 + symbol, 8),
+            144..=255 => self.code(0x190 + symbol - 144, 9),
+            256..=279 => self.code(symbol - 256, 7),
+            _ => self.code(0xc0 + symbol - 280, 8),
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.out.push(self.buffer as u8);
+        }
+        self.out
+    }
+}
+
+const WINDOW: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+// How many earlier positions with the same hash are tried per match.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+fn hash(data: &[u8], i: usize) -> usize {
+    let key = u32::from_le_bytes([data[i], data[i + 1], data[i + 2], 0]);
+    (key.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+// A single fixed Huffman block, with matches found through hash chains of
+// the positions starting with the same three bytes.
+pub fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut writer = BitWriter { out: vec![], buffer: 0, count: 0 };
+    writer.bits(1, 1);
+    writer.bits(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut previous = vec![usize::MAX; WINDOW];
+    let mut i = 0;
+    while i < data.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+        if i + MIN_MATCH <= data.len() {
+            let longest = (data.len() - i).min(MAX_MATCH);
+            let mut candidate = head[hash(data, i)];
+            let mut chain = MAX_CHAIN;
+            while candidate != usize::MAX && i - candidate <= WINDOW && chain > 0 {
+                let length = data[candidate..].iter().zip(&data[i..i + longest]).take_while(|(a, b)| a == b).count();
+                if length > best_length {
+                    (best_length, best_distance) = (length, i - candidate);
+                    if length == longest {
+                        break;
+                    }
+                }
+                candidate = previous[candidate % WINDOW];
+                chain -= 1;
+            }
+        }
+
+        let advance = if best_length >= MIN_MATCH {
+            let symbol = LENGTH_BASE.iter().rposition(|&base| base as usize <= best_length).unwrap();
+            writer.literal(257 + symbol as u32);
+            writer.bits((best_length - LENGTH_BASE[symbol] as usize) as u32, LENGTH_EXTRA[symbol] as u32);
+
+            let symbol = DIST_BASE.iter().rposition(|&base| base as usize <= best_distance).unwrap();
+            writer.code(symbol as u32, 5);
+            writer.bits((best_distance - DIST_BASE[symbol] as usize) as u32, DIST_EXTRA[symbol] as u32);
+            best_length
+        } else {
+            writer.literal(data[i] as u32);
+            1
+        };
+
+        for j in i..i + advance {
+            if j + MIN_MATCH <= data.len() {
+                let h = hash(data, j);
+                previous[j % WINDOW] = head[h];
+                head[h] = j;
+            }
+        }
+        i += advance;
+    }
+
+    writer.literal(256);
+    writer.finish()
+}
+
+pub fn zlib_compress(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![0x78, 0x9c];
+    out.extend_from_slice(&deflate(data));
+    out.extend_from_slice(&adler32(data).to_be_bytes());
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Streams below were produced by Python's zlib.compress.
+
+    #[test]
+    fn decompresses_stored_blocks() {
+        let stream = [0x78, 0x01, 0x01, 0x03, 0x00, 0xfc, 0xff, 0x61, 0x62, 0x63, 0x02, 0x4d, 0x01, 0x27];
+        assert_eq!(zlib_decompress(&stream).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn decompresses_fixed_huffman_blocks() {
+        let stream = [
+            0x78, 0xda, 0x4b, 0x4b, 0xcc, 0xcd, 0xcc, 0x4b, 0x55, 0x48, 0x43, 0xa6, 0x14, 0x01, 0x57, 0x01, 0x07, 0xb2,
+        ];
+        assert_eq!(zlib_decompress(&stream).unwrap(), b"famine famine famine!");
+    }
+
+    #[test]
+    fn decompresses_dynamic_huffman_blocks() {
+        let stream = [
+            0x78, 0xda, 0x6d, 0xd2, 0xbb, 0x0d, 0x80, 0x40, 0x10, 0x03, 0xd1, 0x56, 0xae, 0x00, 0x02, 0x6c, 0xf3, 0x6d,
+            0x07, 0xe9, 0x88, 0x90, 0x90, 0x80, 0xfe, 0x45, 0x01, 0x3b, 0xe9, 0x44, 0x7e, 0xda, 0x1d, 0xdb, 0x71, 0x7f,
+            0xdf, 0xd5, 0xdf, 0x76, 0x9f, 0xed, 0xe8, 0xfd, 0x19, 0x9a, 0x6a, 0x72, 0x4d, 0xa9, 0x69, 0xaa, 0x69, 0xae,
+            0x69, 0xa9, 0x69, 0xad, 0x69, 0xab, 0x69, 0x87, 0xa9, 0x23, 0x34, 0xd8, 0x2f, 0x00, 0x08, 0x04, 0x02, 0x82,
+            0xc0, 0x20, 0x40, 0x08, 0x14, 0x02, 0x86, 0xc0, 0x61, 0x70, 0x98, 0xee, 0x00, 0x0e, 0x83, 0xc3, 0xe0, 0x30,
+            0x38, 0x0c, 0x0e, 0x83, 0xc3, 0xe0, 0x30, 0x38, 0x02, 0x8e, 0x80, 0x23, 0xf4, 0x50, 0xe0, 0x08, 0x38, 0x02,
+            0x8e, 0x80, 0x23, 0xe0, 0x08, 0x38, 0x02, 0x8e, 0x1f, 0xd5, 0x8e, 0x02, 0x80,
+        ];
+        assert_eq!(stream[2] >> 1 & 3, 2);
+        let expected: String = (0..40).map(|i| format!("{} bottles of beer, ", i)).collect();
+        assert_eq!(zlib_decompress(&stream).unwrap(), expected.as_bytes());
+    }
+
+    #[test]
+    fn rejects_corrupt_streams() {
+        let stream = [
+            0x78, 0xda, 0x4b, 0x4b, 0xcc, 0xcd, 0xcc, 0x4b, 0x55, 0x48, 0x43, 0xa6, 0x14, 0x01, 0x57, 0x01, 0x07, 0xb2,
+        ];
+
+        let mut bad_checksum = stream;
+        bad_checksum[17] ^= 1;
+        assert!(zlib_decompress(&bad_checksum).is_err());
+        assert!(inflate(&stream[2..8]).is_err());
+    }
+
+    #[test]
+    fn compresses_repetitive_data() {
+        let data: Vec<u8> = b"famine ".iter().copied().cycle().take(10000).collect();
+        let compressed = zlib_compress(&data);
+        assert_eq!(compressed[2] >> 1 & 3, 1);
+        assert!(compressed.len() < 200, "{} bytes", compressed.len());
+        assert_eq!(zlib_decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn compress_round_trips() {
+        let data: Vec<u8> = (0..70000u32).map(|i| (i * 7 % 251) as u8).collect();
+        assert_eq!(zlib_decompress(&zlib_compress(&data)).unwrap(), data);
+        assert_eq!(zlib_decompress(&zlib_compress(&[])).unwrap(), b"");
+
+        // Mostly literals, with short matches at every distance.
+        let mut x = 1u32;
+        let noise: Vec<u8> = (0..100000).map(|_| {
+            x ^= x << 13;
+            x ^= x >> 17;
+            x ^= x << 5;
+            (x % 16) as u8
+        }).collect();
+        assert_eq!(zlib_decompress(&zlib_compress(&noise)).unwrap(), noise);
+    }
+}
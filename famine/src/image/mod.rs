@@ -0,0 +1,73 @@
+mod bmp;
+mod inflate;
+mod png;
+mod tga;
+
+// The largest width or height the decoders accept, the usual GPU texture
+// limit. Headers can claim anything, and the whole image is allocated
+// before its pixel data is read.
+const MAX_DIMENSION: usize = 16384;
+
+fn check_dimensions(format: &str, width: usize, height: usize) -> Result<(), String> {
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(format!("{} image of {}x{} is larger than {}x{}", format, width, height, MAX_DIMENSION, MAX_DIMENSION));
+    }
+    Ok(())
+}
+
+// RGBA8 pixels, rows from the top of the image down, the layout
+// `ContextType::new_data_texture` expects.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Image {
+    pub fn new(width: usize, height: usize) -> Self {
+        Image {
+            width,
+            height,
+            pixels: vec![0; width * height * 4],
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+        let i = 4 * (y * self.width + x);
+        [self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]]
+    }
+
+    pub fn set_pixel(&mut self, x: usize, y: usize, value: [u8; 4]) {
+        let i = 4 * (y * self.width + x);
+        self.pixels[i..i + 4].copy_from_slice(&value);
+    }
+}
+
+pub fn decode_png(bytes: &[u8]) -> Result<Image, String> {
+    png::decode(bytes)
+}
+
+pub fn encode_png(image: &Image) -> Vec<u8> {
+    png::encode(image)
+}
+
+pub fn decode_tga(bytes: &[u8]) -> Result<Image, String> {
+    tga::decode(bytes)
+}
+
+pub fn decode_bmp(bytes: &[u8]) -> Result<Image, String> {
+    bmp::decode(bytes)
+}
+
+// Picks the decoder from the file's signature. TGA has none, so anything
+// that is not a PNG or BMP is tried as TGA.
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    if bytes.starts_with(&[137, 80, 78, 71]) {
+        decode_png(bytes)
+    } else if bytes.starts_with(b"BM") {
+        decode_bmp(bytes)
+    } else {
+        decode_tga(bytes)
+    }
+}
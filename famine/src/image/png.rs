@@ -0,0 +1,339 @@
+use super::{check_dimensions, inflate, Image};
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+// (x offset, y offset, x step, y step) for each Adam7 pass.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffff_ffffu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { 0xedb8_8320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    interlaced: bool,
+}
+
+impl Header {
+    fn channels(&self) -> usize {
+        match self.color_type {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            _ => 4,
+        }
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.channels() * self.bit_depth as usize
+    }
+
+    fn stride(&self, width: usize) -> usize {
+        (width * self.bits_per_pixel()).div_ceil(8)
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// Reverses the per scanline filters, returning the raw rows.
+fn unfilter(data: &[u8], stride: usize, rows: usize, bytes_per_pixel: usize) -> Result<Vec<u8>, String> {
+    let mut out = vec![0u8; stride * rows];
+    for y in 0..rows {
+        let line = data.get(y * (stride + 1)..(y + 1) * (stride + 1)).ok_or("PNG image data too short")?;
+        let filter = line[0];
+        for x in 0..stride {
+            let a = if x >= bytes_per_pixel { out[y * stride + x - bytes_per_pixel] } else { 0 };
+            let b = if y > 0 { out[(y - 1) * stride + x] } else { 0 };
+            let c = if x >= bytes_per_pixel && y > 0 { out[(y - 1) * stride + x - bytes_per_pixel] } else { 0 };
+            let raw = line[x + 1];
+            out[y * stride + x] = match filter {
+                0 => raw,
+                1 => raw.wrapping_add(a),
+                2 => raw.wrapping_add(b),
+                3 => raw.wrapping_add(((a as u16 + b as u16) / 2) as u8),
+                4 => raw.wrapping_add(paeth(a, b, c)),
+                _ => return Err(format!("Unknown PNG filter type {}", filter)),
+            };
+        }
+    }
+    Ok(out)
+}
+
+// Filters one scanline of RGBA pixels, picking the filter whose output
+// has the smallest sum of absolute values, which usually compresses best.
+fn filter_row(row: &[u8], above: Option<&[u8]>, out: &mut Vec<u8>) {
+    let mut best: Option<(u64, Vec<u8>)> = None;
+    for filter in 0..5u8 {
+        let mut line = Vec::with_capacity(row.len() + 1);
+        line.push(filter);
+        for x in 0..row.len() {
+            let a = if x >= 4 { row[x - 4] } else { 0 };
+            let b = above.map_or(0, |above| above[x]);
+            let c = if x >= 4 { above.map_or(0, |above| above[x - 4]) } else { 0 };
+            line.push(row[x].wrapping_sub(match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                _ => paeth(a, b, c),
+            }));
+        }
+
+        let cost = line[1..].iter().map(|&v| (v as i8).unsigned_abs() as u64).sum();
+        if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+            best = Some((cost, line));
+        }
+    }
+    out.extend_from_slice(&best.unwrap().1);
+}
+
+fn sample(row: &[u8], index: usize, bit_depth: u8) -> u16 {
+    match bit_depth {
+        16 => u16::from_be_bytes([row[2 * index], row[2 * index + 1]]),
+        8 => row[index] as u16,
+        _ => {
+            let bit = index * bit_depth as usize;
+            let shift = 8 - bit_depth as usize - bit % 8;
+            ((row[bit / 8] >> shift) & ((1 << bit_depth) - 1)) as u16
+        }
+    }
+}
+
+struct Palette {
+    colors: Vec<[u8; 3]>,
+    alpha: Vec<u8>,
+}
+
+fn to_rgba(header: &Header, row: &[u8], x: usize, palette: &Palette, transparent: Option<[u16; 3]>) -> [u8; 4] {
+    let depth = header.bit_depth;
+    let max = (1u32 << depth) - 1;
+    let scale = |v: u16| (v as u32 * 255 / max) as u8;
+    let channels = header.channels();
+    let s = |c: usize| sample(row, x * channels + c, depth);
+
+    match header.color_type {
+        0 => {
+            let gray = s(0);
+            let alpha = if transparent == Some([gray; 3]) { 0 } else { 255 };
+            let gray = scale(gray);
+            [gray, gray, gray, alpha]
+        }
+        2 => {
+            let rgb = [s(0), s(1), s(2)];
+            let alpha = if transparent == Some(rgb) { 0 } else { 255 };
+            [scale(rgb[0]), scale(rgb[1]), scale(rgb[2]), alpha]
+        }
+        3 => {
+            let index = s(0) as usize;
+            let [r, g, b] = palette.colors.get(index).copied().unwrap_or([0, 0, 0]);
+            [r, g, b, palette.alpha.get(index).copied().unwrap_or(255)]
+        }
+        4 => {
+            let gray = scale(s(0));
+            [gray, gray, gray, scale(s(1))]
+        }
+        _ => [scale(s(0)), scale(s(1)), scale(s(2)), scale(s(3))],
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    if bytes.len() < 8 || bytes[..8] != SIGNATURE {
+        return Err("Not a PNG file".into());
+    }
+
+    let mut header: Option<Header> = None;
+    let mut palette = Palette { colors: vec![], alpha: vec![] };
+    let mut transparent_chunk: Vec<u8> = vec![];
+    let mut compressed: Vec<u8> = vec![];
+
+    let mut pos = 8;
+    loop {
+        let length_bytes = bytes.get(pos..pos + 4).ok_or("PNG ended before IEND")?;
+        let length = u32::from_be_bytes(length_bytes.try_into().unwrap()) as usize;
+        let end = (pos + 8).checked_add(length).ok_or("PNG chunk runs past the end of the file")?;
+        let chunk = bytes.get(pos + 4..end).ok_or("PNG chunk runs past the end of the file")?;
+        let crc = bytes.get(end..end + 4).ok_or("PNG chunk is missing its CRC")?;
+        if crc32(chunk) != u32::from_be_bytes(crc.try_into().unwrap()) {
+            return Err("PNG chunk CRC mismatch".into());
+        }
+        pos = end + 4;
+
+        let (kind, data) = chunk.split_at(4);
+        match kind {
+            b"IHDR" => {
+                if data.len() != 13 {
+                    return Err("Invalid PNG header".into());
+                }
+                let h = Header {
+                    width: u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize,
+                    height: u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize,
+                    bit_depth: data[8],
+                    color_type: data[9],
+                    interlaced: data[12] == 1,
+                };
+                check_dimensions("PNG", h.width, h.height)?;
+                let valid_depth = match h.color_type {
+                    0 => matches!(h.bit_depth, 1 | 2 | 4 | 8 | 16),
+                    3 => matches!(h.bit_depth, 1 | 2 | 4 | 8),
+                    2 | 4 | 6 => matches!(h.bit_depth, 8 | 16),
+                    _ => false,
+                };
+                if !valid_depth {
+                    return Err(format!("Unsupported PNG color type {} with bit depth {}", h.color_type, h.bit_depth));
+                }
+                header = Some(h);
+            }
+            b"PLTE" => {
+                palette.colors = data.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+            }
+            b"tRNS" => transparent_chunk = data.to_vec(),
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or("PNG is missing its IHDR chunk")?;
+    let transparent = match header.color_type {
+        3 => {
+            palette.alpha = transparent_chunk;
+            None
+        }
+        0 if transparent_chunk.len() >= 2 => {
+            let gray = u16::from_be_bytes([transparent_chunk[0], transparent_chunk[1]]);
+            Some([gray; 3])
+        }
+        2 if transparent_chunk.len() >= 6 => Some([
+            u16::from_be_bytes([transparent_chunk[0], transparent_chunk[1]]),
+            u16::from_be_bytes([transparent_chunk[2], transparent_chunk[3]]),
+            u16::from_be_bytes([transparent_chunk[4], transparent_chunk[5]]),
+        ]),
+        _ => None,
+    };
+
+    let data = inflate::zlib_decompress(&compressed)?;
+    let bytes_per_pixel = header.bits_per_pixel().div_ceil(8);
+    let mut image = Image::new(header.width, header.height);
+
+    let passes: &[(usize, usize, usize, usize)] = if header.interlaced { &ADAM7 } else { &[(0, 0, 1, 1)] };
+    let mut offset = 0;
+    for &(x0, y0, dx, dy) in passes {
+        let pass_width = (header.width + dx - 1 - x0) / dx;
+        let pass_height = (header.height + dy - 1 - y0) / dy;
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+
+        let stride = header.stride(pass_width);
+        let rows = unfilter(data.get(offset..).unwrap_or(&[]), stride, pass_height, bytes_per_pixel)?;
+        offset += (stride + 1) * pass_height;
+
+        for (y, row) in rows.chunks_exact(stride).enumerate() {
+            for x in 0..pass_width {
+                let pixel = to_rgba(&header, row, x, &palette, transparent);
+                image.set_pixel(x0 + x * dx, y0 + y * dy, pixel);
+            }
+        }
+    }
+
+    Ok(image)
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = out.len();
+    out.extend_from_slice(kind);
+    out.extend_from_slice(data);
+    let crc = crc32(&out[start..]);
+    out.extend_from_slice(&crc.to_be_bytes());
+}
+
+pub fn encode(image: &Image) -> Vec<u8> {
+    let mut header = vec![];
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut raw = Vec::with_capacity((image.width * 4 + 1) * image.height);
+    if image.width > 0 {
+        let mut above = None;
+        for row in image.pixels.chunks_exact(image.width * 4) {
+            filter_row(row, above, &mut raw);
+            above = Some(row);
+        }
+    }
+
+    let mut out = SIGNATURE.to_vec();
+    write_chunk(&mut out, b"IHDR", &header);
+    write_chunk(&mut out, b"IDAT", &inflate::zlib_compress(&raw));
+    write_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_oversized_headers() {
+        let mut header = vec![];
+        header.extend_from_slice(&(1u32 << 20).to_be_bytes());
+        header.extend_from_slice(&(1u32 << 20).to_be_bytes());
+        header.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+        let mut png = SIGNATURE.to_vec();
+        write_chunk(&mut png, b"IHDR", &header);
+        write_chunk(&mut png, b"IEND", &[]);
+        assert!(decode(&png).is_err());
+
+        // A chunk length running far past the end of the file.
+        let mut png = SIGNATURE.to_vec();
+        png.extend_from_slice(&u32::MAX.to_be_bytes());
+        png.extend_from_slice(b"IHDR");
+        assert!(decode(&png).is_err());
+    }
+
+    #[test]
+    fn encode_round_trips() {
+        let mut image = Image::new(5, 3);
+        for y in 0..3 {
+            for x in 0..5 {
+                image.set_pixel(x, y, [x as u8 * 50, y as u8 * 100, (x * y) as u8, 255 - x as u8]);
+            }
+        }
+
+        let decoded = decode(&encode(&image)).unwrap();
+        assert_eq!((decoded.width, decoded.height), (5, 3));
+        assert_eq!(decoded.pixels, image.pixels);
+    }
+}
@@ -0,0 +1,175 @@
+use super::{check_dimensions, Image};
+
+fn read_u16(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+// Converts one stored pixel of `depth` bits (BGR order) to RGBA.
+fn to_rgba(pixel: &[u8], depth: u8, grayscale: bool) -> [u8; 4] {
+    match (depth, grayscale) {
+        (8, true) => [pixel[0], pixel[0], pixel[0], 255],
+        (16, true) => [pixel[0], pixel[0], pixel[0], pixel[1]],
+        (15 | 16, false) => {
+            let v = u16::from_le_bytes([pixel[0], pixel[1]]);
+            let scale = |c: u16| ((c & 0x1f) * 255 / 31) as u8;
+            let alpha = if depth == 16 && v & 0x8000 == 0 { 0 } else { 255 };
+            [scale(v >> 10), scale(v >> 5), scale(v), alpha]
+        }
+        (24, false) => [pixel[2], pixel[1], pixel[0], 255],
+        _ => [pixel[2], pixel[1], pixel[0], pixel[3]],
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    if bytes.len() < 18 {
+        return Err("TGA file too short".into());
+    }
+
+    let id_length = bytes[0] as usize;
+    let color_map_type = bytes[1];
+    let image_type = bytes[2];
+    let map_first = read_u16(bytes, 3) as usize;
+    let map_length = read_u16(bytes, 5) as usize;
+    let map_depth = bytes[7];
+    let width = read_u16(bytes, 12) as usize;
+    let height = read_u16(bytes, 14) as usize;
+    let depth = bytes[16];
+    let descriptor = bytes[17];
+    check_dimensions("TGA", width, height)?;
+
+    let (mapped, grayscale) = match image_type {
+        1 | 9 => (true, false),
+        2 | 10 => (false, false),
+        3 | 11 => (false, true),
+        _ => return Err(format!("Unsupported TGA image type {}", image_type)),
+    };
+    let rle = image_type >= 9;
+
+    let valid_depth = match (mapped, grayscale) {
+        (true, _) => depth == 8 && color_map_type == 1 && matches!(map_depth, 15 | 16 | 24 | 32),
+        (false, true) => matches!(depth, 8 | 16),
+        (false, false) => matches!(depth, 15 | 16 | 24 | 32),
+    };
+    if !valid_depth {
+        return Err(format!("Unsupported TGA pixel depth {} for image type {}", depth, image_type));
+    }
+
+    let mut pos = 18 + id_length;
+    let map_entry_size = (map_depth as usize).div_ceil(8);
+    let mut palette: Vec<[u8; 4]> = vec![];
+    if color_map_type == 1 {
+        let map = bytes.get(pos..pos + map_length * map_entry_size).ok_or("TGA color map runs past the end of the file")?;
+        palette = map.chunks_exact(map_entry_size).map(|entry| to_rgba(entry, map_depth, false)).collect();
+        pos += map_length * map_entry_size;
+    }
+
+    let pixel_size = (depth as usize).div_ceil(8);
+    let pixel_count = width * height;
+    // Grown as the data is read, so a header claiming a large image can't
+    // allocate more than the file can fill.
+    let mut stored: Vec<u8> = vec![];
+    if rle {
+        while stored.len() < pixel_count * pixel_size {
+            let packet = *bytes.get(pos).ok_or("TGA run-length data ended early")?;
+            pos += 1;
+            let count = (packet & 0x7f) as usize + 1;
+            if packet & 0x80 != 0 {
+                let pixel = bytes.get(pos..pos + pixel_size).ok_or("TGA run-length data ended early")?;
+                for _ in 0..count {
+                    stored.extend_from_slice(pixel);
+                }
+                pos += pixel_size;
+            } else {
+                let pixels = bytes.get(pos..pos + count * pixel_size).ok_or("TGA run-length data ended early")?;
+                stored.extend_from_slice(pixels);
+                pos += count * pixel_size;
+            }
+        }
+        stored.truncate(pixel_count * pixel_size);
+    } else {
+        stored.extend_from_slice(bytes.get(pos..pos + pixel_count * pixel_size).ok_or("TGA pixel data too short")?);
+    }
+
+    let right_to_left = descriptor & 0x10 != 0;
+    let top_to_bottom = descriptor & 0x20 != 0;
+
+    let mut image = Image::new(width, height);
+    for (i, pixel) in stored.chunks_exact(pixel_size).enumerate() {
+        let rgba = if mapped {
+            let index = (pixel[0] as usize).checked_sub(map_first).ok_or("TGA color index below the color map")?;
+            *palette.get(index).ok_or("TGA color index past the end of the color map")?
+        } else {
+            to_rgba(pixel, depth, grayscale)
+        };
+
+        let (x, y) = (i % width, i / width);
+        let x = if right_to_left { width - 1 - x } else { x };
+        let y = if top_to_bottom { y } else { height - 1 - y };
+        image.set_pixel(x, y, rgba);
+    }
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An image without an id or color map.
+    fn tga(image_type: u8, width: u16, height: u16, depth: u8, descriptor: u8, data: &[u8]) -> Vec<u8> {
+        let mut out = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        out.extend_from_slice(&width.to_le_bytes());
+        out.extend_from_slice(&height.to_le_bytes());
+        out.extend_from_slice(&[depth, descriptor]);
+        out.extend_from_slice(data);
+        out
+    }
+
+    // Two rows of two BGR pixels.
+    const PIXELS: [u8; 12] = [0, 0, 255, 0, 255, 0, 255, 0, 0, 255, 255, 255];
+
+    #[test]
+    fn rows_are_bottom_up_unless_flagged() {
+        let image = decode(&tga(2, 2, 2, 24, 0, &PIXELS)).unwrap();
+        assert_eq!(image.pixel(0, 1), [255, 0, 0, 255]);
+        assert_eq!(image.pixel(1, 1), [0, 255, 0, 255]);
+        assert_eq!(image.pixel(0, 0), [0, 0, 255, 255]);
+        assert_eq!(image.pixel(1, 0), [255, 255, 255, 255]);
+
+        let top_down = decode(&tga(2, 2, 2, 24, 0x20, &PIXELS)).unwrap();
+        assert_eq!(top_down.pixel(0, 0), [255, 0, 0, 255]);
+        assert_eq!(top_down.pixel(1, 1), [255, 255, 255, 255]);
+
+        let right_to_left = decode(&tga(2, 2, 2, 24, 0x30, &PIXELS)).unwrap();
+        assert_eq!(right_to_left.pixel(1, 0), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn decodes_run_length_packets() {
+        // A run of three red pixels, then one raw blue pixel with alpha.
+        let data = [0x82, 0, 0, 255, 128, 0x00, 255, 0, 0, 64];
+        let image = decode(&tga(10, 2, 2, 32, 0x20, &data)).unwrap();
+        assert_eq!(image.pixel(0, 0), [255, 0, 0, 128]);
+        assert_eq!(image.pixel(1, 0), [255, 0, 0, 128]);
+        assert_eq!(image.pixel(0, 1), [255, 0, 0, 128]);
+        assert_eq!(image.pixel(1, 1), [0, 0, 255, 64]);
+
+        // A run spilling past the last pixel is cut off.
+        let image = decode(&tga(10, 1, 1, 24, 0, &[0x85, 1, 2, 3])).unwrap();
+        assert_eq!(image.pixels, vec![3, 2, 1, 255]);
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        assert!(decode(&tga(2, 2, 2, 24, 0, &PIXELS)[..17]).is_err());
+        assert!(decode(&tga(2, 2, 2, 24, 0, &PIXELS[..11])).is_err());
+        assert!(decode(&tga(10, 2, 2, 24, 0, &[0x82, 0, 0, 255])).is_err());
+        assert!(decode(&tga(10, 2, 2, 24, 0, &[0x01, 0, 0, 255])).is_err());
+    }
+
+    #[test]
+    fn rejects_oversized_headers() {
+        assert!(decode(&tga(2, u16::MAX, u16::MAX, 32, 0, &[])).is_err());
+        assert!(decode(&tga(10, 16384, 16384, 32, 0, &[0xff, 0, 0, 0, 0])).is_err());
+    }
+}
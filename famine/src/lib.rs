@@ -1,10 +1,21 @@
-use std::f32::consts::PI;
-use linalg::{Mat4, Vec4};
-
+use std::collections::HashMap;
+use linalg::{Mat4, Vec3, Vec4};
+use time::FrameInfo;
+use vertex::{AttributeType, VertexAttribute, VertexLayout};
+
+pub mod assets;
+pub mod executor;
+pub mod image;
 pub mod linalg;
 pub mod numerical;
+pub mod obj;
+mod primitives;
 pub mod shaders;
+pub mod time;
+pub mod transform;
+pub mod vertex;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -27,75 +38,189 @@ impl Color {
     }
 }
 
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Mesh {
-    pub vertices: Vec<f32>
+    // Interleaved vertex attributes as described by `layout`.
+    pub vertices: Vec<f32>,
+    // Triangles as vertex numbers. Empty when `vertices` is already a
+    // triangle list.
+    pub indices: Vec<u32>,
+    pub layout: VertexLayout,
 }
 
 impl Mesh {
+    // Position and uv vertices, see `VertexLayout::position_uv`.
     pub fn new(vertices: Vec<f32>) -> Self {
-        Mesh { vertices }
+        Mesh { vertices, indices: vec![], layout: VertexLayout::default() }
+    }
+
+    pub fn indexed(vertices: Vec<f32>, indices: Vec<u32>) -> Self {
+        Mesh { vertices, indices, layout: VertexLayout::default() }
+    }
+
+    pub fn with_layout(vertices: Vec<f32>, indices: Vec<u32>, layout: VertexLayout) -> Self {
+        Mesh { vertices, indices, layout }
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        !self.indices.is_empty()
     }
 
-    pub fn sphere(radius: f32, rings: u32, slices: u32) -> Option<Self> {
-        if rings < 3 || slices < 3 || radius <= 0.0 {
-            return None;
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len().checked_div(self.layout.vertex_size()).unwrap_or(0)
+    }
+
+    // Vertices drawn, i.e. three per triangle.
+    pub fn element_count(&self) -> usize {
+        if self.is_indexed() { self.indices.len() } else { self.vertex_count() }
+    }
+
+    // Replaces the v_normal attribute, adding it to the layout as three
+    // floats if needed. Smooth normals average the faces around each
+    // position, weighted by their angle there, so they stay continuous
+    // across uv seams. Flat normals need separate vertices per triangle, so
+    // they leave the mesh unindexed. Meshes without a v_position attribute
+    // are unchanged, and indices out of range panic.
+    pub fn compute_normals(&mut self, mode: NormalMode) {
+        let vertex_size = self.layout.vertex_size();
+        if vertex_size == 0 || self.layout.find("v_position").is_none() {
+            return;
         }
 
-        let mut vertices: Vec<f32> = vec![];
-        
-        let angle_per_ring: f32 = PI / rings as f32;
-        let angle_per_slice: f32 = 2.0 * PI / slices as f32;
-        for i in 1..rings+1 {
-            let phi: f32 = PI/2.0 + angle_per_ring * i as f32;
-
-            let top_y = radius * phi.sin();
-            let bottom_y = radius * (phi - angle_per_ring).sin();
-
-            for j in 1..slices+1 {
-                let theta: f32 = 2.0 * PI + angle_per_slice * j as f32;
-
-                let top_x1: f32 = radius * (theta - angle_per_slice).cos() * phi.cos();
-                let top_z1: f32 = radius * (theta - angle_per_slice).sin() * phi.cos();
-                let bottom_x1: f32 = radius * (theta - angle_per_slice).cos() * (phi - angle_per_ring).cos();
-                let bottom_z1: f32 = radius * (theta - angle_per_slice).sin() * (phi - angle_per_ring).cos();
-
-                let top_x2: f32 = radius * theta.cos() * phi.cos();
-                let top_z2: f32 = radius * theta.sin() * phi.cos();
-                let bottom_x2: f32 = radius * theta.cos() * (phi - angle_per_ring).cos();
-                let bottom_z2: f32 = radius * theta.sin() * (phi - angle_per_ring).cos();
-
-                let left_u = (theta - angle_per_slice) / (2.0 * PI);
-                let right_u = theta / (2.0 * PI);
-
-                let top_v = top_y;
-                let bottom_v = bottom_y;
-
-                vertices.append(&mut vec![
-                    top_x1, top_y, top_z1, left_u, top_v,
-                    top_x2, top_y, top_z2, right_u, top_v,
-                    bottom_x1, bottom_y, bottom_z1, left_u, bottom_v,
-
-                    bottom_x2, bottom_y, bottom_z2, right_u, bottom_v,
-                    bottom_x1, bottom_y, bottom_z1, left_u, bottom_v,
-                    top_x2, top_y, top_z2, right_u, top_v,
-                ]);
+        let positions: Vec<Vec3> = self.vertices.chunks_exact(vertex_size)
+            .map(|v| {
+                let p = self.layout.read(v, "v_position").unwrap();
+                Vec3::new([p[0], p[1], p[2]])
+            })
+            .collect();
+        let triangles: Vec<[usize; 3]> = if self.is_indexed() {
+            self.indices.chunks_exact(3).map(|t| [t[0] as usize, t[1] as usize, t[2] as usize]).collect()
+        } else {
+            (0..positions.len() / 3).map(|t| [3 * t, 3 * t + 1, 3 * t + 2]).collect()
+        };
+        let face_normal = |t: &[usize; 3]| {
+            (positions[t[1]] - positions[t[0]]).cross(&(positions[t[2]] - positions[t[0]]))
+        };
+
+        let normals: Vec<Vec3> = match mode {
+            NormalMode::Smooth => {
+                // Adding zero turns -0.0 into 0.0 so they share a key.
+                let key = |p: &Vec3| p.data.map(|c| (c + 0.0).to_bits());
+                let mut sums: HashMap<[u32; 3], Vec3> = HashMap::new();
+                for t in &triangles {
+                    let normal = face_normal(t).normalize();
+                    for k in 0..3 {
+                        let p = positions[t[k]];
+                        let a = (positions[t[(k + 1) % 3]] - p).normalize();
+                        let b = (positions[t[(k + 2) % 3]] - p).normalize();
+                        let angle = a.dot(&b).clamp(-1.0, 1.0).acos();
+                        *sums.entry(key(&p)).or_insert(Vec3::ZERO) += normal * angle;
+                    }
+                }
+                positions.iter()
+                    .map(|p| sums.get(&key(p)).copied().unwrap_or(Vec3::ZERO).normalize())
+                    .collect()
+            }
+            NormalMode::Flat => {
+                self.vertices = triangles.iter()
+                    .flatten()
+                    .flat_map(|&i| self.vertices[i * vertex_size..(i + 1) * vertex_size].iter().copied())
+                    .collect();
+                self.indices.clear();
+                triangles.iter().flat_map(|t| [face_normal(t).normalize(); 3]).collect()
             }
+        };
+
+        self.set_float_normals(&normals);
+    }
+
+    // Writes one normal per vertex, first converting the layout's v_normal
+    // to three floats at the end of the vertex if it is anything else.
+    fn set_float_normals(&mut self, normals: &[Vec3]) {
+        let is_float3 = |a: &VertexAttribute| a.kind == AttributeType::Float && a.components == 3;
+        if !self.layout.find("v_normal").is_some_and(|(_, a)| is_float3(a)) {
+            let old_size = self.layout.vertex_size();
+            let kept: Vec<(usize, VertexAttribute)> = self.layout.offsets()
+                .filter(|(_, a)| a.name != "v_normal")
+                .map(|(offset, a)| (offset / 4, a.clone()))
+                .collect();
+
+            let mut layout = VertexLayout::empty();
+            for (_, a) in &kept {
+                layout = layout.with(&a.name, a.components, a.kind, a.normalized);
+            }
+            self.layout = layout.with("v_normal", 3, AttributeType::Float, false);
+
+            self.vertices = self.vertices.chunks_exact(old_size)
+                .flat_map(|v| {
+                    kept.iter()
+                        .flat_map(|(offset, a)| v[*offset..*offset + a.size() / 4].iter().copied())
+                        .chain([0.0; 3])
+                })
+                .collect();
         }
 
+        let vertex_size = self.layout.vertex_size();
+        let offset = self.layout.find("v_normal").unwrap().0 / 4;
+        for (vertex, normal) in self.vertices.chunks_exact_mut(vertex_size).zip(normals) {
+            vertex[offset..offset + 3].copy_from_slice(&normal.data);
+        }
+    }
+
+    // Glyph quads for the 16x8 font atlas, starting at '!'.
+    pub fn text(text: &str, x: f32, y: f32, width: f32, height: f32) -> Self {
+        let mut vertices: Vec<f32> = vec![];
 
-        Some(Mesh { vertices })
+        let char_w: f32 = width / text.len() as f32;
+        let u_per_column: f32 = 1.0 / 16.0;
+        let v_per_row: f32 = 1.0 / 8.0;
+        for (i, c) in text.chars().enumerate() {
+            if c <= ' ' {
+                continue;
+            }
+
+            let row: f32 = ((c as u32 - '!' as u32) as f32 / 16.0).floor();
+            let column: f32 = ((c as u32 - '!' as u32) % 16) as f32;
+
+            let char_x: f32 = x + i as f32 * char_w;
+            let char_u1: f32 = u_per_column * column;
+            let char_u2: f32 = char_u1 + u_per_column;
+            let char_v1: f32 = v_per_row * (row + 1.0);
+            let char_v2: f32 = v_per_row * row;
+            vertices.append(&mut vec![
+                char_x,          y + height, 0.0, char_u1, char_v2,
+                char_x,          y,          0.0, char_u1, char_v1,
+                char_x + char_w, y + height, 0.0, char_u2, char_v2,
+                char_x,          y,          0.0, char_u1, char_v1,
+                char_x + char_w, y,          0.0, char_u2, char_v1,
+                char_x + char_w, y + height, 0.0, char_u2, char_v2,
+            ]);
+        }
+
+        Mesh::new(vertices)
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalMode {
+    Smooth,
+    Flat,
+}
+
 pub trait ContextType {
     type Shader;
     type Texture;
+    // A mesh stored on the GPU, for geometry that doesn't change between draws.
+    type MeshBuffer;
 
     // Create
     fn new(title: &str, width: usize, height: usize) -> Self;
     fn new_shader(&self, vert_src: &str, frag_str: &str) -> Self::Shader;
     fn new_image_texture(&self, name: &str) -> impl std::future::Future<Output = Self::Texture>;
     fn new_data_texture(&self, width: i32, height: i32, data: Vec<u8>) -> Self::Texture;
+    // The contents of an asset, found the same way as image textures.
+    fn load_asset(&self, name: &str) -> impl std::future::Future<Output = Result<Vec<u8>, String>>;
+    fn upload_mesh(&self, mesh: &Mesh) -> Self::MeshBuffer;
     
     // Setup
     fn use_shader(&self, shader: &Self::Shader);
@@ -107,6 +232,7 @@ pub trait ContextType {
     // Execute
     fn clear(&self, r: f32, g: f32, b: f32, a: f32);
     fn draw_mesh(&self, mesh: &Mesh);
+    fn draw_mesh_buffer(&self, mesh: &Self::MeshBuffer);
     fn draw_text(&self, text: &str, x: f32, y: f32, width: f32, height: f32, color: Color);
 
     // Read
@@ -118,6 +244,6 @@ pub trait ContextType {
 
 pub trait Application<Context: ContextType> {
     fn new() -> impl std::future::Future<Output = Self>;
-    fn update(&mut self) -> impl std::future::Future<Output = ()>;
+    fn update(&mut self, frame: FrameInfo) -> impl std::future::Future<Output = ()>;
     fn get_window(&self) -> &Context;
 }
\ No newline at end of file
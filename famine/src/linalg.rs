@@ -1,13 +1,260 @@
+use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use crate::Color;
+
+// Operations shared by every vector size. Products and quotients of two
+// vectors are component-wise.
+macro_rules! impl_vector {
+    ($name:ident, $n:literal) => {
+        impl $name {
+            pub const ZERO: Self = $name { data: [0.0; $n] };
+
+            pub fn new(data: [f32; $n]) -> Self {
+                $name { data }
+            }
+
+            pub fn splat(value: f32) -> Self {
+                $name { data: [value; $n] }
+            }
+
+            pub fn x(&self) -> f32 {
+                self.data[0]
+            }
+
+            pub fn y(&self) -> f32 {
+                self.data[1]
+            }
+
+            pub fn dot(&self, other: &Self) -> f32 {
+                self.data.iter().zip(other.data.iter()).map(|(a, b)| a * b).sum()
+            }
+
+            pub fn length_squared(&self) -> f32 {
+                self.dot(self)
+            }
+
+            pub fn length(&self) -> f32 {
+                self.length_squared().sqrt()
+            }
+
+            // The zero vector is returned unchanged.
+            pub fn normalize(&self) -> Self {
+                let length = self.length();
+                if length == 0.0 { *self } else { *self / length }
+            }
+
+            pub fn lerp(&self, other: &Self, t: f32) -> Self {
+                *self + (*other - *self) * t
+            }
+
+            fn map(self, f: impl Fn(f32) -> f32) -> Self {
+                $name { data: self.data.map(f) }
+            }
+
+            fn zip(self, other: Self, f: impl Fn(f32, f32) -> f32) -> Self {
+                let mut data = self.data;
+                for (a, b) in data.iter_mut().zip(other.data) {
+                    *a = f(*a, b);
+                }
+                $name { data }
+            }
+        }
+
+        impl From<[f32; $n]> for $name {
+            fn from(data: [f32; $n]) -> Self {
+                $name { data }
+            }
+        }
+
+        impl From<$name> for [f32; $n] {
+            fn from(v: $name) -> Self {
+                v.data
+            }
+        }
+
+        impl Index<usize> for $name {
+            type Output = f32;
+
+            fn index(&self, i: usize) -> &f32 {
+                &self.data[i]
+            }
+        }
+
+        impl IndexMut<usize> for $name {
+            fn index_mut(&mut self, i: usize) -> &mut f32 {
+                &mut self.data[i]
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+
+            fn add(self, other: Self) -> Self {
+                self.zip(other, |a, b| a + b)
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+
+            fn sub(self, other: Self) -> Self {
+                self.zip(other, |a, b| a - b)
+            }
+        }
+
+        impl Mul for $name {
+            type Output = Self;
+
+            fn mul(self, other: Self) -> Self {
+                self.zip(other, |a, b| a * b)
+            }
+        }
+
+        impl Mul<f32> for $name {
+            type Output = Self;
+
+            fn mul(self, scalar: f32) -> Self {
+                self.map(|a| a * scalar)
+            }
+        }
+
+        impl Mul<$name> for f32 {
+            type Output = $name;
+
+            fn mul(self, v: $name) -> $name {
+                v * self
+            }
+        }
+
+        impl Div for $name {
+            type Output = Self;
+
+            fn div(self, other: Self) -> Self {
+                self.zip(other, |a, b| a / b)
+            }
+        }
+
+        impl Div<f32> for $name {
+            type Output = Self;
+
+            fn div(self, scalar: f32) -> Self {
+                self.map(|a| a / scalar)
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+
+            fn neg(self) -> Self {
+                self.map(|a| -a)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: Self) {
+                *self = *self + other;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) {
+                *self = *self - other;
+            }
+        }
+
+        impl MulAssign for $name {
+            fn mul_assign(&mut self, other: Self) {
+                *self = *self * other;
+            }
+        }
+
+        impl MulAssign<f32> for $name {
+            fn mul_assign(&mut self, scalar: f32) {
+                *self = *self * scalar;
+            }
+        }
+
+        impl DivAssign for $name {
+            fn div_assign(&mut self, other: Self) {
+                *self = *self / other;
+            }
+        }
+
+        impl DivAssign<f32> for $name {
+            fn div_assign(&mut self, scalar: f32) {
+                *self = *self / scalar;
+            }
+        }
+    };
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec2 {
+    pub data: [f32; 2],
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vec3 {
+    pub data: [f32; 3],
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Vec4 {
     pub data: [f32; 4],
 }
 
+impl_vector!(Vec2, 2);
+impl_vector!(Vec3, 3);
+impl_vector!(Vec4, 4);
+
+impl Vec3 {
+    pub fn z(&self) -> f32 {
+        self.data[2]
+    }
+
+    pub fn cross(&self, other: &Self) -> Self {
+        let [ax, ay, az] = self.data;
+        let [bx, by, bz] = other.data;
+        Vec3 { data: [
+            ay * bz - az * by,
+            az * bx - ax * bz,
+            ax * by - ay * bx,
+        ]}
+    }
+
+    pub fn extend(&self, w: f32) -> Vec4 {
+        Vec4 { data: [self.data[0], self.data[1], self.data[2], w] }
+    }
+}
+
 impl Vec4 {
-    pub fn new(data: [f32; 4]) -> Self {
-        Vec4 { data }
+    pub fn z(&self) -> f32 {
+        self.data[2]
+    }
+
+    pub fn w(&self) -> f32 {
+        self.data[3]
+    }
+
+    pub fn truncate(&self) -> Vec3 {
+        Vec3 { data: [self.data[0], self.data[1], self.data[2]] }
     }
 }
 
+impl From<Color> for Vec4 {
+    fn from(color: Color) -> Self {
+        color.as_vec4()
+    }
+}
+
+impl From<&Color> for Vec4 {
+    fn from(color: &Color) -> Self {
+        color.as_vec4()
+    }
+}
+
+// Column-major, as uploaded by `set_uniform_mat4`: `get(i, j)` is row i of column j.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Mat4 {
     pub data: [f32; 16],
 }
@@ -44,6 +291,15 @@ impl Mat4 {
         ]}
     }
 
+    pub fn translate(x: f32, y: f32, z: f32) -> Self {
+        Mat4 { data: [
+            1., 0., 0., 0.,
+            0., 1., 0., 0.,
+            0., 0., 1., 0.,
+            x,  y,  z,  1.,
+        ]}
+    }
+
     pub fn rotate_x(theta: f32) -> Self {
         Mat4 { data: [
             1., 0.,          0.,           0.,
@@ -71,6 +327,46 @@ impl Mat4 {
         ]}
     }
 
+    // The camera matrices follow OpenGL: view space is right-handed with the
+    // camera looking down -Z and +Y up, and clip space z runs from -1 at the
+    // near plane to 1 at the far plane, as `gl_Position` expects.
+
+    // `fovy` is the full vertical field of view in radians, `aspect` is width / height.
+    pub fn perspective(fovy: f32, aspect: f32, near: f32, far: f32) -> Self {
+        let f = 1.0 / (fovy / 2.0).tan();
+        let depth = near - far;
+        Mat4 { data: [
+            f / aspect, 0., 0.,                         0.,
+            0.,         f,  0.,                         0.,
+            0.,         0., (far + near) / depth,       -1.,
+            0.,         0., 2.0 * far * near / depth,   0.,
+        ]}
+    }
+
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        let (width, height, depth) = (right - left, top - bottom, far - near);
+        Mat4 { data: [
+            2.0 / width,               0.,                         0.,                     0.,
+            0.,                        2.0 / height,               0.,                     0.,
+            0.,                        0.,                         -2.0 / depth,           0.,
+            -(right + left) / width,   -(top + bottom) / height,   -(far + near) / depth,  1.,
+        ]}
+    }
+
+    // View matrix for a camera at `eye` facing `target`. `up` only needs to be
+    // roughly up, but must not be parallel to the view direction.
+    pub fn look_at(eye: &Vec3, target: &Vec3, up: &Vec3) -> Self {
+        let forward = (*target - *eye).normalize();
+        let side = forward.cross(up).normalize();
+        let up = side.cross(&forward);
+        Mat4 { data: [
+            side.x(),         up.x(),         -forward.x(),       0.,
+            side.y(),         up.y(),         -forward.y(),       0.,
+            side.z(),         up.z(),         -forward.z(),       0.,
+            -side.dot(eye),   -up.dot(eye),   forward.dot(eye),   1.,
+        ]}
+    }
+
     pub fn set(&mut self, i: usize, j: usize, value: f32) {
         self.data[4 * j + i] = value;
     }
@@ -79,17 +375,883 @@ impl Mat4 {
         self.data[4 * j + i]
     }
 
-    pub fn mul(&self, other: &Self) -> Self {
+    // `self * other` written to `out` instead of a new matrix, for filling
+    // preallocated buffers. Like `*` and `*=`, this is the GLSL product, so
+    // `other` is applied first.
+    #[inline]
+    pub fn mul_into(&self, other: &Self, out: &mut Self) {
+        kernels::mul(&self.data, &other.data, &mut out.data);
+    }
+
+    pub fn transpose(&self) -> Self {
         let mut res = Mat4::zero();
         for i in 0..4 {
             for j in 0..4 {
-                res.set(i, j, self.get(0, j) * other.get(i, 0) 
-                    + self.get(1, j) * other.get(i, 1) 
-                    + self.get(2, j) * other.get(i, 2) 
-                    + self.get(3, j) * other.get(i, 3)
-                );
+                res.set(i, j, self.get(j, i));
+            }
+        }
+        res
+    }
+
+    // 2x2 determinants of the top two rows and of the bottom two rows, which
+    // both the determinant and the inverse are built from.
+    fn minors(&self) -> ([f32; 6], [f32; 6]) {
+        let a = |i, j| self.get(i, j);
+        let top = [
+            a(0, 0) * a(1, 1) - a(1, 0) * a(0, 1),
+            a(0, 0) * a(1, 2) - a(1, 0) * a(0, 2),
+            a(0, 0) * a(1, 3) - a(1, 0) * a(0, 3),
+            a(0, 1) * a(1, 2) - a(1, 1) * a(0, 2),
+            a(0, 1) * a(1, 3) - a(1, 1) * a(0, 3),
+            a(0, 2) * a(1, 3) - a(1, 2) * a(0, 3),
+        ];
+        let bottom = [
+            a(2, 0) * a(3, 1) - a(3, 0) * a(2, 1),
+            a(2, 0) * a(3, 2) - a(3, 0) * a(2, 2),
+            a(2, 0) * a(3, 3) - a(3, 0) * a(2, 3),
+            a(2, 1) * a(3, 2) - a(3, 1) * a(2, 2),
+            a(2, 1) * a(3, 3) - a(3, 1) * a(2, 3),
+            a(2, 2) * a(3, 3) - a(3, 2) * a(2, 3),
+        ];
+        (top, bottom)
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let (s, c) = self.minors();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    // Returns None when the matrix is singular (or so close to it that the
+    // determinant is no longer a normal float).
+    pub fn inverse(&self) -> Option<Self> {
+        let (s, c) = self.minors();
+        let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        if !det.is_normal() {
+            return None;
+        }
+
+        let a = |i, j| self.get(i, j);
+        let rows = [
+            [
+                a(1, 1) * c[5] - a(1, 2) * c[4] + a(1, 3) * c[3],
+                -a(0, 1) * c[5] + a(0, 2) * c[4] - a(0, 3) * c[3],
+                a(3, 1) * s[5] - a(3, 2) * s[4] + a(3, 3) * s[3],
+                -a(2, 1) * s[5] + a(2, 2) * s[4] - a(2, 3) * s[3],
+            ],
+            [
+                -a(1, 0) * c[5] + a(1, 2) * c[2] - a(1, 3) * c[1],
+                a(0, 0) * c[5] - a(0, 2) * c[2] + a(0, 3) * c[1],
+                -a(3, 0) * s[5] + a(3, 2) * s[2] - a(3, 3) * s[1],
+                a(2, 0) * s[5] - a(2, 2) * s[2] + a(2, 3) * s[1],
+            ],
+            [
+                a(1, 0) * c[4] - a(1, 1) * c[2] + a(1, 3) * c[0],
+                -a(0, 0) * c[4] + a(0, 1) * c[2] - a(0, 3) * c[0],
+                a(3, 0) * s[4] - a(3, 1) * s[2] + a(3, 3) * s[0],
+                -a(2, 0) * s[4] + a(2, 1) * s[2] - a(2, 3) * s[0],
+            ],
+            [
+                -a(1, 0) * c[3] + a(1, 1) * c[1] - a(1, 2) * c[0],
+                a(0, 0) * c[3] - a(0, 1) * c[1] + a(0, 2) * c[0],
+                -a(3, 0) * s[3] + a(3, 1) * s[1] - a(3, 2) * s[0],
+                a(2, 0) * s[3] - a(2, 1) * s[1] + a(2, 2) * s[0],
+            ],
+        ];
+
+        let mut res = Mat4::zero();
+        for (i, row) in rows.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                res.set(i, j, value / det);
+            }
+        }
+        Some(res)
+    }
+
+    // The upper-left 3x3, i.e. the matrix without its translation.
+    pub fn upper_left(&self) -> Mat3 {
+        let mut res = Mat3::zero();
+        for i in 0..3 {
+            for j in 0..3 {
+                res.set(i, j, self.get(i, j));
+            }
+        }
+        res
+    }
+
+    // Transforms normals the way this matrix transforms positions, keeping
+    // them perpendicular to surfaces under non-uniform scaling. None when the
+    // upper-left 3x3 is singular.
+    pub fn normal_matrix(&self) -> Option<Mat3> {
+        self.upper_left().inverse().map(|m| m.transpose())
+    }
+}
+
+// `a * b` is the GLSL product, so `b` is applied first.
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    #[inline]
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut res = Mat4::zero();
+        kernels::mul(&self.data, &other.data, &mut res.data);
+        res
+    }
+}
+
+impl Mul<&Mat4> for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: &Mat4) -> Mat4 {
+        self * *other
+    }
+}
+
+impl Mul<Mat4> for &Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: Mat4) -> Mat4 {
+        *self * other
+    }
+}
+
+impl Mul<&Mat4> for &Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, other: &Mat4) -> Mat4 {
+        *self * *other
+    }
+}
+
+impl Mul<Vec4> for Mat4 {
+    type Output = Vec4;
+
+    #[inline]
+    fn mul(self, v: Vec4) -> Vec4 {
+        let mut res = Vec4::ZERO;
+        kernels::transform(&self.data, &v.data, &mut res.data);
+        res
+    }
+}
+
+impl Mul<Vec4> for &Mat4 {
+    type Output = Vec4;
+
+    fn mul(self, v: Vec4) -> Vec4 {
+        *self * v
+    }
+}
+
+impl Mul<&Vec4> for &Mat4 {
+    type Output = Vec4;
+
+    fn mul(self, v: &Vec4) -> Vec4 {
+        *self * *v
+    }
+}
+
+// `a *= &b` is `a = &a * &b`, so `b` is applied first.
+impl MulAssign<&Mat4> for Mat4 {
+    #[inline]
+    fn mul_assign(&mut self, other: &Mat4) {
+        let a = self.data;
+        kernels::mul(&a, &other.data, &mut self.data);
+    }
+}
+
+impl MulAssign<Mat4> for Mat4 {
+    fn mul_assign(&mut self, other: Mat4) {
+        *self *= &other;
+    }
+}
+
+// The GLSL products `a * b` and `m * v` on column-major arrays, written so
+// LLVM vectorizes them without any `std::arch` code.
+mod kernels {
+    #[inline]
+    pub fn mul(a: &[f32; 16], b: &[f32; 16], out: &mut [f32; 16]) {
+        for j in 0..4 {
+            for i in 0..4 {
+                out[4 * j + i] = a[i] * b[4 * j]
+                    + a[4 + i] * b[4 * j + 1]
+                    + a[8 + i] * b[4 * j + 2]
+                    + a[12 + i] * b[4 * j + 3];
+            }
+        }
+    }
+
+    #[inline]
+    pub fn transform(m: &[f32; 16], v: &[f32; 4], out: &mut [f32; 4]) {
+        for (i, value) in out.iter_mut().enumerate() {
+            *value = m[i] * v[0] + m[4 + i] * v[1] + m[8 + i] * v[2] + m[12 + i] * v[3];
+        }
+    }
+}
+
+// Column-major like `Mat4`. GLSL `mat3` uniforms can be fed from a `Mat4`
+// built with `Mat4::from`, since `mat3(m)` takes the upper-left 3x3.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat3 {
+    pub data: [f32; 9],
+}
+
+impl Mat3 {
+    pub fn new(data: [f32; 9]) -> Self {
+        Mat3 { data }
+    }
+
+    pub fn zero() -> Self {
+        Mat3 { data: [0.; 9] }
+    }
+
+    pub fn identity() -> Self {
+        Mat3 { data: [
+            1., 0., 0.,
+            0., 1., 0.,
+            0., 0., 1.,
+        ]}
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, value: f32) {
+        self.data[3 * j + i] = value;
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f32 {
+        self.data[3 * j + i]
+    }
+
+    pub fn column(&self, j: usize) -> Vec3 {
+        Vec3::new([self.get(0, j), self.get(1, j), self.get(2, j)])
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut res = Mat3::zero();
+        for i in 0..3 {
+            for j in 0..3 {
+                res.set(i, j, self.get(j, i));
             }
         }
         res
     }
-}
\ No newline at end of file
+
+    pub fn determinant(&self) -> f32 {
+        self.column(0).dot(&self.column(1).cross(&self.column(2)))
+    }
+
+    // Returns None when the matrix is singular, as for `Mat4::inverse`.
+    pub fn inverse(&self) -> Option<Self> {
+        let (c0, c1, c2) = (self.column(0), self.column(1), self.column(2));
+        let det = c0.dot(&c1.cross(&c2));
+        if !det.is_normal() {
+            return None;
+        }
+
+        // The rows of the inverse are the cross products of the other two columns.
+        let rows = [c1.cross(&c2), c2.cross(&c0), c0.cross(&c1)];
+        let mut res = Mat3::zero();
+        for (i, row) in rows.iter().enumerate() {
+            for j in 0..3 {
+                res.set(i, j, row[j] / det);
+            }
+        }
+        Some(res)
+    }
+}
+
+impl From<Mat3> for Mat4 {
+    fn from(m: Mat3) -> Self {
+        let mut res = Mat4::identity();
+        for i in 0..3 {
+            for j in 0..3 {
+                res.set(i, j, m.get(i, j));
+            }
+        }
+        res
+    }
+}
+
+impl Mul<&Mat3> for &Mat3 {
+    type Output = Mat3;
+
+    fn mul(self, other: &Mat3) -> Mat3 {
+        let mut res = Mat3::zero();
+        for i in 0..3 {
+            for j in 0..3 {
+                res.set(i, j, (0..3).map(|k| self.get(i, k) * other.get(k, j)).sum());
+            }
+        }
+        res
+    }
+}
+
+impl Mul<Vec3> for &Mat3 {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        let mut res = Vec3::ZERO;
+        for i in 0..3 {
+            res.data[i] = (0..3).map(|j| self.get(i, j) * v.data[j]).sum();
+        }
+        res
+    }
+}
+
+// Unit quaternion rotation. Positive angles rotate counter-clockwise when
+// looking down the axis towards the origin (right-hand rule), which is the
+// opposite direction to `Mat4::rotate_x/y/z`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub const IDENTITY: Self = Quat { x: 0.0, y: 0.0, z: 0.0, w: 1.0 };
+
+    pub fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Quat { x, y, z, w }
+    }
+
+    // `axis` doesn't need to be normalized.
+    pub fn from_axis_angle(axis: &Vec3, angle: f32) -> Self {
+        let axis = axis.normalize();
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Quat { x: axis.x() * sin, y: axis.y() * sin, z: axis.z() * sin, w: cos }
+    }
+
+    // Rotates about X, then Y, then Z.
+    pub fn from_euler(x: f32, y: f32, z: f32) -> Self {
+        let qx = Quat::from_axis_angle(&Vec3::new([1.0, 0.0, 0.0]), x);
+        let qy = Quat::from_axis_angle(&Vec3::new([0.0, 1.0, 0.0]), y);
+        let qz = Quat::from_axis_angle(&Vec3::new([0.0, 0.0, 1.0]), z);
+        qz * qy * qx
+    }
+
+    // The rotation part of `m`, which must not contain any scaling.
+    pub fn from_mat4(m: &Mat4) -> Self {
+        let a = |i, j| m.get(i, j);
+        let trace = a(0, 0) + a(1, 1) + a(2, 2);
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quat::new((a(2, 1) - a(1, 2)) / s, (a(0, 2) - a(2, 0)) / s, (a(1, 0) - a(0, 1)) / s, s / 4.0)
+        } else if a(0, 0) > a(1, 1) && a(0, 0) > a(2, 2) {
+            let s = (1.0 + a(0, 0) - a(1, 1) - a(2, 2)).sqrt() * 2.0;
+            Quat::new(s / 4.0, (a(0, 1) + a(1, 0)) / s, (a(0, 2) + a(2, 0)) / s, (a(2, 1) - a(1, 2)) / s)
+        } else if a(1, 1) > a(2, 2) {
+            let s = (1.0 + a(1, 1) - a(0, 0) - a(2, 2)).sqrt() * 2.0;
+            Quat::new((a(0, 1) + a(1, 0)) / s, s / 4.0, (a(1, 2) + a(2, 1)) / s, (a(0, 2) - a(2, 0)) / s)
+        } else {
+            let s = (1.0 + a(2, 2) - a(0, 0) - a(1, 1)).sqrt() * 2.0;
+            Quat::new((a(0, 2) + a(2, 0)) / s, (a(1, 2) + a(2, 1)) / s, s / 4.0, (a(1, 0) - a(0, 1)) / s)
+        };
+        q.normalize()
+    }
+
+    pub fn to_mat4(&self) -> Mat4 {
+        let Quat { x, y, z, w } = *self;
+        Mat4 { data: [
+            1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y + w * z),       2.0 * (x * z - w * y),       0.,
+            2.0 * (x * y - w * z),       1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z + w * x),       0.,
+            2.0 * (x * z + w * y),       2.0 * (y * z - w * x),       1.0 - 2.0 * (x * x + y * y), 0.,
+            0.,                          0.,                          0.,                          1.,
+        ]}
+    }
+
+    pub fn dot(&self, other: &Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn length(&self) -> f32 {
+        self.dot(self).sqrt()
+    }
+
+    // A zero quaternion is returned as the identity.
+    pub fn normalize(&self) -> Self {
+        let length = self.length();
+        if length == 0.0 {
+            return Quat::IDENTITY;
+        }
+        Quat::new(self.x / length, self.y / length, self.z / length, self.w / length)
+    }
+
+    pub fn conjugate(&self) -> Self {
+        Quat::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn inverse(&self) -> Self {
+        let length_squared = self.dot(self);
+        let c = self.conjugate();
+        Quat::new(c.x / length_squared, c.y / length_squared, c.z / length_squared, c.w / length_squared)
+    }
+
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        let axis = Vec3::new([self.x, self.y, self.z]);
+        let t = axis.cross(v) * 2.0;
+        *v + t * self.w + axis.cross(&t)
+    }
+
+    // Interpolates along the shorter arc at constant angular speed.
+    pub fn slerp(&self, other: &Self, t: f32) -> Self {
+        let mut cos = self.dot(other);
+        let mut other = *other;
+        if cos < 0.0 {
+            other = -other;
+            cos = -cos;
+        }
+
+        // Nearly parallel: sin(theta) is too small to divide by, and a
+        // normalized lerp is indistinguishable.
+        let (a, b) = if cos > 0.9995 {
+            (1.0 - t, t)
+        } else {
+            let theta = cos.acos();
+            let sin = theta.sin();
+            (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+        };
+
+        Quat::new(
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b,
+            self.w * a + other.w * b,
+        ).normalize()
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl From<Quat> for Mat4 {
+    fn from(q: Quat) -> Self {
+        q.to_mat4()
+    }
+}
+
+impl From<&Mat4> for Quat {
+    fn from(m: &Mat4) -> Self {
+        Quat::from_mat4(m)
+    }
+}
+
+// `a * b` rotates by `b` first, then by `a`, as with matrices.
+impl Mul for Quat {
+    type Output = Quat;
+
+    fn mul(self, o: Quat) -> Quat {
+        Quat::new(
+            self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+            self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+        )
+    }
+}
+
+impl MulAssign for Quat {
+    fn mul_assign(&mut self, other: Quat) {
+        *self = *self * other;
+    }
+}
+
+impl Mul<Vec3> for Quat {
+    type Output = Vec3;
+
+    fn mul(self, v: Vec3) -> Vec3 {
+        self.rotate(&v)
+    }
+}
+
+impl Neg for Quat {
+    type Output = Quat;
+
+    fn neg(self) -> Quat {
+        Quat::new(-self.x, -self.y, -self.z, -self.w)
+    }
+}
+
+// Dense matrix of any size, column-major like `Mat4`, for linear systems too
+// big or too dynamic for the fixed size types.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatN {
+    pub rows: usize,
+    pub columns: usize,
+    pub data: Vec<f32>,
+}
+
+impl MatN {
+    pub fn zero(rows: usize, columns: usize) -> Self {
+        MatN { rows, columns, data: vec![0.0; rows * columns] }
+    }
+
+    pub fn identity(size: usize) -> Self {
+        let mut res = MatN::zero(size, size);
+        for i in 0..size {
+            res.set(i, i, 1.0);
+        }
+        res
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, value: f32) {
+        self.data[self.rows * j + i] = value;
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f32 {
+        self.data[self.rows * j + i]
+    }
+
+    // `out = self * v`.
+    pub fn transform(&self, v: &[f32], out: &mut [f32]) {
+        assert_eq!(v.len(), self.columns, "Vector length doesn't match the matrix columns");
+        assert_eq!(out.len(), self.rows, "Output length doesn't match the matrix rows");
+        out.fill(0.0);
+        for (j, x) in v.iter().enumerate() {
+            for (i, value) in out.iter_mut().enumerate() {
+                *value += self.get(i, j) * x;
+            }
+        }
+    }
+
+    // Factors a square matrix for solving. None when it is singular.
+    pub fn lu(self) -> Option<LuDecomposition> {
+        LuDecomposition::new(self)
+    }
+
+    // Solves `self * x = b`. None when the matrix is singular. Factor with
+    // `lu` instead to solve for several right hand sides.
+    pub fn solve(&self, b: &[f32]) -> Option<Vec<f32>> {
+        let mut x = b.to_vec();
+        self.clone().lu()?.solve_in_place(&mut x);
+        Some(x)
+    }
+}
+
+// `P * A = L * U` by Gaussian elimination with partial pivoting. L (with an
+// implicit unit diagonal) and U share one matrix.
+#[derive(Clone, Debug)]
+pub struct LuDecomposition {
+    lu: MatN,
+    // Row `i` of `P * A` is row `pivots[i]` of `A`.
+    pivots: Vec<usize>,
+    swaps: usize,
+}
+
+impl LuDecomposition {
+    pub fn new(matrix: MatN) -> Option<Self> {
+        assert_eq!(matrix.rows, matrix.columns, "LU decomposition needs a square matrix");
+        let n = matrix.rows;
+        let mut lu = matrix;
+        let mut pivots: Vec<usize> = (0..n).collect();
+        let mut swaps = 0;
+
+        for k in 0..n {
+            let pivot = (k..n).max_by(|&a, &b| lu.get(a, k).abs().total_cmp(&lu.get(b, k).abs())).unwrap();
+            if !lu.get(pivot, k).is_normal() {
+                return None;
+            }
+            if pivot != k {
+                for j in 0..n {
+                    let (a, b) = (lu.get(k, j), lu.get(pivot, j));
+                    lu.set(k, j, b);
+                    lu.set(pivot, j, a);
+                }
+                pivots.swap(k, pivot);
+                swaps += 1;
+            }
+
+            let diagonal = lu.get(k, k);
+            for i in k + 1..n {
+                let factor = lu.get(i, k) / diagonal;
+                lu.set(i, k, factor);
+                for j in k + 1..n {
+                    lu.set(i, j, lu.get(i, j) - factor * lu.get(k, j));
+                }
+            }
+        }
+
+        Some(LuDecomposition { lu, pivots, swaps })
+    }
+
+    pub fn size(&self) -> usize {
+        self.lu.rows
+    }
+
+    pub fn determinant(&self) -> f32 {
+        let product: f32 = (0..self.size()).map(|i| self.lu.get(i, i)).product();
+        if self.swaps.is_multiple_of(2) { product } else { -product }
+    }
+
+    // Replaces `b` with the `x` solving `A * x = b`.
+    pub fn solve_in_place(&self, b: &mut [f32]) {
+        let n = self.size();
+        assert_eq!(b.len(), n, "Right hand side doesn't match the matrix size");
+
+        let permuted: Vec<f32> = self.pivots.iter().map(|&p| b[p]).collect();
+        b.copy_from_slice(&permuted);
+
+        for i in 0..n {
+            for j in 0..i {
+                b[i] -= self.lu.get(i, j) * b[j];
+            }
+        }
+        for i in (0..n).rev() {
+            for j in i + 1..n {
+                b[i] -= self.lu.get(i, j) * b[j];
+            }
+            b[i] /= self.lu.get(i, i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    fn assert_close(a: &Mat4, b: &Mat4, tolerance: f32) {
+        for (x, y) in a.data.iter().zip(b.data.iter()) {
+            assert!((x - y).abs() <= tolerance, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    #[allow(clippy::op_ref)]
+    fn products_follow_glsl_order() {
+        let t = Mat4::translate(1.0, 2.0, 3.0);
+        let s = Mat4::scale(2.0, 2.0, 2.0);
+
+        // Scaled first, then moved.
+        let p = t * s * Vec4::new([1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(p, Vec4::new([3.0, 4.0, 5.0, 1.0]));
+
+        assert_eq!(t * s, &t * &s);
+        assert_eq!(t * s, t * &s);
+        assert_eq!(t * s, &t * s);
+        assert_eq!(&t * Vec4::new([0.0, 0.0, 0.0, 1.0]), &t * &Vec4::new([0.0, 0.0, 0.0, 1.0]));
+        assert_eq!(t * Mat4::identity(), t);
+    }
+
+    #[test]
+    fn perspective_matches_glu() {
+        // gluPerspective(90, 2, 1, 3)
+        let m = Mat4::perspective(std::f32::consts::FRAC_PI_2, 2.0, 1.0, 3.0);
+        assert_close(&m, &Mat4::new([
+            0.5, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, -2.0, -1.0,
+            0.0, 0.0, -3.0, 0.0,
+        ]), 1e-6);
+
+        let ndc_z = |z: f32| {
+            let clip = m * Vec4::new([0.0, 0.0, z, 1.0]);
+            clip[2] / clip[3]
+        };
+        assert!((ndc_z(-1.0) + 1.0).abs() < 1e-6);
+        assert!((ndc_z(-3.0) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn orthographic_matches_gl() {
+        // glOrtho(-1, 3, -2, 2, 1, 5)
+        let m = Mat4::orthographic(-1.0, 3.0, -2.0, 2.0, 1.0, 5.0);
+        assert_close(&m, &Mat4::new([
+            0.5, 0.0, 0.0, 0.0,
+            0.0, 0.5, 0.0, 0.0,
+            0.0, 0.0, -0.5, 0.0,
+            -0.5, 0.0, -1.5, 1.0,
+        ]), 1e-6);
+
+        assert_eq!(m * Vec4::new([-1.0, -2.0, -1.0, 1.0]), Vec4::new([-1.0, -1.0, -1.0, 1.0]));
+        assert_eq!(m * Vec4::new([3.0, 2.0, -5.0, 1.0]), Vec4::new([1.0, 1.0, 1.0, 1.0]));
+    }
+
+    #[test]
+    fn look_at_matches_glu() {
+        let up = Vec3::new([0.0, 1.0, 0.0]);
+
+        // gluLookAt(0, 0, 5, 0, 0, 0, 0, 1, 0)
+        let m = Mat4::look_at(&Vec3::new([0.0, 0.0, 5.0]), &Vec3::ZERO, &up);
+        assert_close(&m, &Mat4::translate(0.0, 0.0, -5.0), 1e-6);
+
+        // gluLookAt(5, 0, 0, 0, 0, 0, 0, 1, 0)
+        let m = Mat4::look_at(&Vec3::new([5.0, 0.0, 0.0]), &Vec3::ZERO, &up);
+        assert_close(&m, &Mat4::new([
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            -1.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, -5.0, 1.0,
+        ]), 1e-6);
+    }
+
+    #[test]
+    fn inverse_undoes_the_matrix() {
+        let m = Mat4::translate(1.0, -2.0, 3.0) * Mat4::rotate_y(0.7) * Mat4::scale(2.0, 0.5, 3.0)
+            * Mat4::perspective(1.0, 1.5, 0.1, 10.0);
+        let inverse = m.inverse().unwrap();
+        assert_close(&(m * inverse), &Mat4::identity(), 1e-5);
+        assert_close(&(inverse * m), &Mat4::identity(), 1e-5);
+        assert!((m.determinant() * inverse.determinant() - 1.0).abs() < 1e-4);
+
+        let m3 = m.upper_left();
+        let product = &m3 * &m3.inverse().unwrap();
+        assert_close(&Mat4::from(product), &Mat4::identity(), 1e-5);
+    }
+
+    #[test]
+    fn singular_matrices_have_no_inverse() {
+        let flat = Mat4::scale(1.0, 0.0, 1.0);
+        assert_eq!(flat.determinant(), 0.0);
+        assert!(flat.inverse().is_none());
+        assert!(flat.upper_left().inverse().is_none());
+        assert!(flat.normal_matrix().is_none());
+
+        // Two equal rows.
+        let mut m = Mat4::identity();
+        m.set(1, 0, 1.0);
+        m.set(1, 1, 0.0);
+        assert!(m.inverse().is_none());
+    }
+
+    #[test]
+    fn normal_matrix_keeps_normals_perpendicular() {
+        let m = Mat4::rotate_z(0.3) * Mat4::scale(4.0, 1.0, 1.0);
+        let normal_matrix = m.normal_matrix().unwrap();
+
+        // A 45 degree slope and its normal.
+        let tangent = Vec3::new([1.0, 1.0, 0.0]);
+        let normal = Vec3::new([1.0, -1.0, 0.0]);
+        let transformed_tangent = (m * tangent.extend(0.0)).truncate();
+        let transformed_normal = &normal_matrix * normal;
+        assert!(transformed_tangent.dot(&transformed_normal).abs() < 1e-5);
+
+        // Transforming the normal like a position would not be perpendicular.
+        let wrong = (m * normal.extend(0.0)).truncate();
+        assert!(transformed_tangent.dot(&wrong).abs() > 1.0);
+    }
+
+    fn assert_vec3_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn quat_matrix_round_trip() {
+        let quats = [
+            Quat::IDENTITY,
+            Quat::from_axis_angle(&Vec3::new([1.0, 2.0, 3.0]), 0.8),
+            Quat::from_euler(0.3, -1.2, 2.5),
+            // Half turns, which take the other branches of `from_mat4`.
+            Quat::from_axis_angle(&Vec3::new([1.0, 0.0, 0.0]), PI),
+            Quat::from_axis_angle(&Vec3::new([0.0, 1.0, 0.0]), PI),
+            Quat::from_axis_angle(&Vec3::new([0.0, 0.0, 1.0]), PI),
+        ];
+        for q in quats {
+            let back = Quat::from_mat4(&q.to_mat4());
+            // q and -q are the same rotation.
+            assert!((back.dot(&q).abs() - 1.0).abs() < 1e-5, "{:?} != {:?}", back, q);
+        }
+    }
+
+    #[test]
+    fn quat_turns_the_other_way_to_rotate_matrices() {
+        let axes = [
+            (Vec3::new([1.0, 0.0, 0.0]), Mat4::rotate_x(0.9)),
+            (Vec3::new([0.0, 1.0, 0.0]), Mat4::rotate_y(0.9)),
+            (Vec3::new([0.0, 0.0, 1.0]), Mat4::rotate_z(0.9)),
+        ];
+        for (axis, matrix) in axes {
+            assert_close(&Quat::from_axis_angle(&axis, -0.9).to_mat4(), &matrix, 1e-6);
+        }
+    }
+
+    #[test]
+    fn slerp_endpoints_and_midpoint() {
+        let axis = Vec3::new([0.0, 0.0, 1.0]);
+        let a = Quat::from_axis_angle(&axis, 0.2);
+        let b = Quat::from_axis_angle(&axis, 1.4);
+
+        assert!((a.slerp(&b, 0.0).dot(&a) - 1.0).abs() < 1e-6);
+        assert!((a.slerp(&b, 1.0).dot(&b) - 1.0).abs() < 1e-6);
+        assert!((a.slerp(&b, 0.5).dot(&Quat::from_axis_angle(&axis, 0.8)) - 1.0).abs() < 1e-6);
+
+        // -b is the same rotation, and the shorter arc is still taken.
+        assert!((a.slerp(&-b, 0.5).dot(&Quat::from_axis_angle(&axis, 0.8)).abs() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn from_euler_rotates_x_then_y_then_z() {
+        let q = Quat::from_euler(PI / 2.0, PI / 2.0, 0.0);
+        // X takes +Y to +Z, then Y takes +Z to +X.
+        assert_vec3_close(q.rotate(&Vec3::new([0.0, 1.0, 0.0])), Vec3::new([1.0, 0.0, 0.0]));
+
+        let q = Quat::from_euler(0.0, PI / 2.0, PI / 2.0);
+        // Y takes +X to -Z, which Z leaves alone.
+        assert_vec3_close(q.rotate(&Vec3::new([1.0, 0.0, 0.0])), Vec3::new([0.0, 0.0, -1.0]));
+
+        let (x, y, z) = (0.4, -0.7, 1.1);
+        let expected = Quat::from_axis_angle(&Vec3::new([0.0, 0.0, 1.0]), z)
+            * Quat::from_axis_angle(&Vec3::new([0.0, 1.0, 0.0]), y)
+            * Quat::from_axis_angle(&Vec3::new([1.0, 0.0, 0.0]), x);
+        assert!((Quat::from_euler(x, y, z).dot(&expected) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn mul_into_matches_the_operators() {
+        let a = Mat4::translate(1.0, 2.0, 3.0) * Mat4::rotate_x(0.5);
+        let b = Mat4::scale(2.0, 3.0, 4.0) * Mat4::rotate_z(1.0);
+
+        let mut out = Mat4::zero();
+        a.mul_into(&b, &mut out);
+        assert_eq!(out, a * b);
+
+        let mut c = a;
+        c *= b;
+        assert_eq!(c, a * b);
+        assert_ne!(a * b, b * a);
+    }
+
+    fn matn(rows: &[&[f32]]) -> MatN {
+        let mut m = MatN::zero(rows.len(), rows[0].len());
+        for (i, row) in rows.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                m.set(i, j, *value);
+            }
+        }
+        m
+    }
+
+    #[test]
+    fn lu_solve_pivots_past_zeros() {
+        // Elimination without pivoting would divide by the leading zero.
+        let m = matn(&[
+            &[0.0, 2.0, 1.0],
+            &[1.0, 1.0, 0.0],
+            &[3.0, 0.0, 1.0],
+        ]);
+        let x = m.solve(&[7.0, 3.0, 6.0]).unwrap();
+        for (value, expected) in x.iter().zip([1.0, 2.0, 3.0]) {
+            assert!((value - expected).abs() < 1e-5, "{:?}", x);
+        }
+
+        let mut product = [0.0; 3];
+        m.transform(&x, &mut product);
+        assert!(product.iter().zip([7.0, 3.0, 6.0]).all(|(a, b)| (a - b).abs() < 1e-5));
+
+        // det = 0 * (1 - 0) - 2 * (1 - 0) + 1 * (0 - 3)
+        assert!((m.lu().unwrap().determinant() + 5.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn singular_matn_has_no_solution() {
+        let m = matn(&[
+            &[1.0, 2.0, 3.0],
+            &[2.0, 4.0, 6.0],
+            &[0.0, 1.0, 1.0],
+        ]);
+        assert!(m.solve(&[1.0, 2.0, 3.0]).is_none());
+        assert!(MatN::zero(2, 2).lu().is_none());
+    }
+}
@@ -1,23 +1,841 @@
-pub struct EulerSolver<T> {
+use std::ops::{Add, Mul};
+
+use crate::{linalg::{MatN, Vec2, Vec3, Vec4}, time::FixedTimestep};
+
+// The equations a solver integrates: writes the derivative of every state
+// element at time `t` to `out`. For the second order solvers `state` holds
+// the positions and `out` receives the accelerations.
+//
+// Implemented for closures `FnMut(t, &[T], &mut [T])`. Implement it on
+// application types to drive solvers from their fields.
+pub trait System<T> {
+    fn derivatives(&mut self, t: f64, state: &[T], out: &mut [T]);
+}
+
+impl<T, F> System<T> for F where
+    F: FnMut(f64, &[T], &mut [T]) {
+    fn derivatives(&mut self, t: f64, state: &[T], out: &mut [T]) {
+        self(t, state, out)
+    }
+}
+
+pub trait OdeSolver<T> {
+    fn state(&self) -> &[T];
+    // Total time stepped since the solver was created.
+    fn time(&self) -> f64;
+    fn step(&mut self, dt: f32);
+
+    // Steps with `timestep`'s fixed dt for the real time `delta` and
+    // returns the interpolation alpha.
+    fn advance(&mut self, timestep: &mut FixedTimestep, delta: f32) -> f32 {
+        timestep.advance(delta, |dt| self.step(dt))
+    }
+}
+
+// Size of a state element, for the adaptive solvers' error estimates.
+pub trait Magnitude {
+    fn magnitude(&self) -> f32;
+}
+
+impl Magnitude for f32 {
+    fn magnitude(&self) -> f32 {
+        self.abs()
+    }
+}
+
+impl Magnitude for Vec2 {
+    fn magnitude(&self) -> f32 {
+        self.length()
+    }
+}
+
+impl Magnitude for Vec3 {
+    fn magnitude(&self) -> f32 {
+        self.length()
+    }
+}
+
+impl Magnitude for Vec4 {
+    fn magnitude(&self) -> f32 {
+        self.length()
+    }
+}
+
+pub struct EulerSolver<T, S> {
     pub state: Vec<T>,
     pub derivatives: Vec<T>,
-    derivative_func: fn(&Vec<T>, &mut Vec<T>),
+    pub time: f64,
+    pub system: S,
 }
 
-impl<T> EulerSolver<T> where
-    T: Copy + core::ops::Mul<f32> + std::ops::AddAssign<<T>::Output> {
-    pub fn new(initial_state: Vec<T>, derivative_func: fn(&Vec<T>, &mut Vec<T>)) -> Self {
+impl<T, S> EulerSolver<T, S> where
+    T: Copy + core::ops::Mul<f32> + std::ops::AddAssign<<T>::Output>,
+    S: System<T> {
+    pub fn from_system(initial_state: Vec<T>, system: S) -> Self {
         EulerSolver {
             state: initial_state.clone(),
             derivatives: initial_state,
-            derivative_func
+            time: 0.0,
+            system,
         }
     }
+}
+
+// Separate from `from_system` so that closure argument types are inferred.
+impl<T, F> EulerSolver<T, F> where
+    T: Copy + core::ops::Mul<f32> + std::ops::AddAssign<<T>::Output>,
+    F: FnMut(f64, &[T], &mut [T]) {
+    pub fn new(initial_state: Vec<T>, derivative_func: F) -> Self {
+        EulerSolver::from_system(initial_state, derivative_func)
+    }
+}
+
+impl<T, S> OdeSolver<T> for EulerSolver<T, S> where
+    T: Copy + core::ops::Mul<f32> + std::ops::AddAssign<<T>::Output>,
+    S: System<T> {
+    fn state(&self) -> &[T] {
+        &self.state
+    }
 
-    pub fn step(&mut self, dt: f32) {
-        (self.derivative_func)(&self.state, &mut self.derivatives);
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn step(&mut self, dt: f32) {
+        self.system.derivatives(self.time, &self.state, &mut self.derivatives);
         self.state.iter_mut()
             .zip(self.derivatives.iter())
             .for_each(|(val, derivative)| { *val += *derivative * dt; });
+        self.time += dt as f64;
+    }
+}
+
+// `f32::max` that keeps NaN instead of dropping it.
+fn max_or_nan(a: f32, b: f32) -> f32 {
+    if a.is_nan() || a >= b { a } else { b }
+}
+
+// `out = state + sum(k[j] * h * weights[j])`, the stage sums of the Runge-Kutta methods.
+fn combine<T>(state: &[T], k: &[Vec<T>], weights: &[f32], h: f32, out: &mut [T]) where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T> {
+    for (i, value) in out.iter_mut().enumerate() {
+        *value = k.iter()
+            .zip(weights)
+            .filter(|(_, &w)| w != 0.0)
+            .fold(state[i], |sum, (k, &w)| sum + k[i] * (h * w));
+    }
+}
+
+// Classic fourth order Runge-Kutta.
+pub struct Rk4Solver<T, S> {
+    pub state: Vec<T>,
+    pub time: f64,
+    pub system: S,
+    k: [Vec<T>; 4],
+    stage: Vec<T>,
+}
+
+impl<T, S> Rk4Solver<T, S> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T>,
+    S: System<T> {
+    pub fn from_system(initial_state: Vec<T>, system: S) -> Self {
+        Rk4Solver {
+            k: std::array::from_fn(|_| initial_state.clone()),
+            stage: initial_state.clone(),
+            state: initial_state,
+            time: 0.0,
+            system,
+        }
+    }
+}
+
+impl<T, F> Rk4Solver<T, F> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T>,
+    F: FnMut(f64, &[T], &mut [T]) {
+    pub fn new(initial_state: Vec<T>, derivative_func: F) -> Self {
+        Rk4Solver::from_system(initial_state, derivative_func)
+    }
+}
+
+impl<T, S> OdeSolver<T> for Rk4Solver<T, S> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T>,
+    S: System<T> {
+    fn state(&self) -> &[T] {
+        &self.state
+    }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn step(&mut self, dt: f32) {
+        const STAGES: [(usize, f32); 3] = [(0, 0.5), (1, 0.5), (2, 1.0)];
+
+        self.system.derivatives(self.time, &self.state, &mut self.k[0]);
+        for (prev, c) in STAGES {
+            combine(&self.state, &self.k[prev..=prev], &[c], dt, &mut self.stage);
+            self.system.derivatives(self.time + (c * dt) as f64, &self.stage, &mut self.k[prev + 1]);
+        }
+
+        combine(&self.state, &self.k, &[1.0 / 6.0, 1.0 / 3.0, 1.0 / 3.0, 1.0 / 6.0], dt, &mut self.stage);
+        std::mem::swap(&mut self.state, &mut self.stage);
+        self.time += dt as f64;
+    }
+}
+
+// Dormand-Prince tableau: stage times, stage coefficients, the fifth order
+// solution, and its difference from the embedded fourth order one.
+const DP_C: [f32; 6] = [1.0 / 5.0, 3.0 / 10.0, 4.0 / 5.0, 8.0 / 9.0, 1.0, 1.0];
+const DP_A: [[f32; 6]; 6] = [
+    [1.0 / 5.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+    [3.0 / 40.0, 9.0 / 40.0, 0.0, 0.0, 0.0, 0.0],
+    [44.0 / 45.0, -56.0 / 15.0, 32.0 / 9.0, 0.0, 0.0, 0.0],
+    [19372.0 / 6561.0, -25360.0 / 2187.0, 64448.0 / 6561.0, -212.0 / 729.0, 0.0, 0.0],
+    [9017.0 / 3168.0, -355.0 / 33.0, 46732.0 / 5247.0, 49.0 / 176.0, -5103.0 / 18656.0, 0.0],
+    [35.0 / 384.0, 0.0, 500.0 / 1113.0, 125.0 / 192.0, -2187.0 / 6784.0, 11.0 / 84.0],
+];
+const DP_ERROR: [f32; 7] = [
+    71.0 / 57600.0, 0.0, -71.0 / 16695.0, 71.0 / 1920.0, -17253.0 / 339200.0, 22.0 / 525.0, -1.0 / 40.0,
+];
+
+// Adaptive fifth order Runge-Kutta (Dormand-Prince). Each `step(dt)` covers
+// all of `dt` in as many substeps as `tolerance` needs, rejecting and
+// retrying substeps whose error estimate is too large or not finite. The
+// substep size carries over between calls.
+pub struct Rk45Solver<T, S> {
+    pub state: Vec<T>,
+    pub time: f64,
+    pub system: S,
+    // Allowed error per substep, relative to the size of each element but
+    // never tighter than `tolerance` itself for elements near zero.
+    pub tolerance: f32,
+    // Substeps this small are accepted whatever their error, so stiff
+    // systems can't stall the solver.
+    pub min_step: f32,
+    pub accepted_steps: u64,
+    pub rejected_steps: u64,
+    step_size: Option<f32>,
+    k: [Vec<T>; 7],
+    stage: Vec<T>,
+    error: Vec<T>,
+}
+
+impl<T, S> Rk45Solver<T, S> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T> + Magnitude,
+    S: System<T> {
+    pub fn from_system(initial_state: Vec<T>, tolerance: f32, system: S) -> Self {
+        Rk45Solver {
+            time: 0.0,
+            system,
+            tolerance,
+            min_step: 1e-6,
+            accepted_steps: 0,
+            rejected_steps: 0,
+            step_size: None,
+            k: std::array::from_fn(|_| initial_state.clone()),
+            stage: initial_state.clone(),
+            error: initial_state.clone(),
+            state: initial_state,
+        }
+    }
+
+    // The substep size the next step will try first, None until the first
+    // step picks one.
+    pub fn step_size(&self) -> Option<f32> {
+        self.step_size
+    }
+
+    // The usual starting substep (Hairer, Norsett and Wanner, II.4): small
+    // enough that an Euler step barely moves the state, and that the
+    // derivatives change by little more than `tolerance` over it.
+    fn initial_step_size(&mut self, t: f64) -> f32 {
+        let tolerance = self.tolerance;
+        let scaled = |v: &[T], y: &[T]| {
+            v.iter().zip(y).map(|(v, y)| v.magnitude() / (tolerance * (1.0 + y.magnitude()))).fold(0.0, max_or_nan)
+        };
+
+        self.system.derivatives(t, &self.state, &mut self.k[0]);
+        let d0 = scaled(&self.state, &self.state);
+        let d1 = scaled(&self.k[0], &self.state);
+        let h0 = if d0 < 1e-5 || d1 < 1e-5 { 1e-6 } else { 0.01 * d0 / d1 };
+
+        combine(&self.state, &self.k[..1], &[1.0], h0, &mut self.stage);
+        self.system.derivatives(t + h0 as f64, &self.stage, &mut self.k[1]);
+        for (e, (k1, k0)) in self.error.iter_mut().zip(self.k[1].iter().zip(&self.k[0])) {
+            *e = *k1 + *k0 * -1.0;
+        }
+        let d2 = scaled(&self.error, &self.state) / h0;
+
+        let larger = d1.max(d2);
+        let h1 = if larger <= 1e-15 { (h0 * 1e-3).max(1e-6) } else { (0.01 / larger).powf(0.2) };
+        (100.0 * h0).min(h1).max(self.min_step)
+    }
+
+    // Leaves the fifth order solution in `stage` and returns the error
+    // estimate scaled so that 1 is exactly at tolerance, or infinity if the
+    // solution overflowed.
+    fn try_step(&mut self, t: f64, h: f32) -> f32 {
+        self.system.derivatives(t, &self.state, &mut self.k[0]);
+        for (s, (a, c)) in DP_A.iter().zip(DP_C).enumerate() {
+            combine(&self.state, &self.k[..=s], a, h, &mut self.stage);
+            self.system.derivatives(t + (c * h) as f64, &self.stage, &mut self.k[s + 1]);
+        }
+
+        // `stage` now holds the last row of `DP_A`, which is the solution.
+        for (i, e) in self.error.iter_mut().enumerate() {
+            *e = self.k.iter()
+                .zip(DP_ERROR)
+                .filter(|(_, w)| *w != 0.0)
+                .map(|(k, w)| k[i] * (h * w))
+                .reduce(|a, b| a + b)
+                .unwrap();
+        }
+
+        if self.stage.iter().any(|y| !y.magnitude().is_finite()) {
+            return f32::INFINITY;
+        }
+
+        self.error.iter()
+            .zip(self.state.iter().zip(self.stage.iter()))
+            .map(|(e, (y0, y1))| {
+                let scale = self.tolerance * (1.0 + y0.magnitude().max(y1.magnitude()));
+                e.magnitude() / scale
+            })
+            .fold(0.0, max_or_nan)
+    }
+}
+
+impl<T, F> Rk45Solver<T, F> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T> + Magnitude,
+    F: FnMut(f64, &[T], &mut [T]) {
+    pub fn new(initial_state: Vec<T>, tolerance: f32, derivative_func: F) -> Self {
+        Rk45Solver::from_system(initial_state, tolerance, derivative_func)
+    }
+}
+
+impl<T, S> OdeSolver<T> for Rk45Solver<T, S> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T> + Magnitude,
+    S: System<T> {
+    fn state(&self) -> &[T] {
+        &self.state
+    }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    // Panics unless `dt` is finite, which no number of substeps can cover.
+    fn step(&mut self, dt: f32) {
+        assert!(dt.is_finite(), "Rk45 step of {} can't be covered", dt);
+        let start = self.time;
+        // In f64 so that small substeps still add up on a large `dt`.
+        let mut elapsed = 0.0;
+        while elapsed < dt as f64 {
+            let remaining = (dt as f64 - elapsed) as f32;
+            let size = match self.step_size {
+                Some(size) => size,
+                None => self.initial_step_size(start + elapsed),
+            };
+            let last = size >= remaining;
+            let h = if last { remaining } else { size };
+            let error = self.try_step(start + elapsed, h);
+
+            // The usual safety factor and growth limits for a fifth order
+            // method, shrinking as far as allowed when the error is unknown.
+            let factor = if !error.is_finite() {
+                0.2
+            } else if error == 0.0 {
+                5.0
+            } else {
+                (0.9 * error.powf(-0.2)).clamp(0.2, 5.0)
+            };
+            let next = (h * factor).max(self.min_step);
+
+            if error <= 1.0 || h <= self.min_step {
+                std::mem::swap(&mut self.state, &mut self.stage);
+                self.accepted_steps += 1;
+                elapsed = if last { dt as f64 } else { elapsed + h as f64 };
+                // A substep cut short to land on `dt` says nothing about
+                // how large the next one can be.
+                self.step_size = Some(if h < size { size.max(next) } else { next });
+            } else {
+                self.rejected_steps += 1;
+                self.step_size = Some(next);
+            }
+        }
+        self.time = start + dt as f64;
+    }
+}
+
+// Solvers for `x'' = a(t, x)` that keep positions and velocities apart. Both
+// are symplectic, so the energy of conservative systems oscillates around
+// its true value instead of drifting away like it does with `EulerSolver`.
+pub trait SecondOrderSolver<T> {
+    fn positions(&self) -> &[T];
+    fn velocities(&self) -> &[T];
+    fn time(&self) -> f64;
+    fn step(&mut self, dt: f32);
+
+    // As `OdeSolver::advance`.
+    fn advance(&mut self, timestep: &mut FixedTimestep, delta: f32) -> f32 {
+        timestep.advance(delta, |dt| self.step(dt))
+    }
+}
+
+// `v += a(t, x) * dt` for both second order solvers.
+fn kick<T, S>(system: &mut S, t: f64, positions: &[T], velocities: &mut [T], accelerations: &mut [T], dt: f32) where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T>,
+    S: System<T> {
+    system.derivatives(t, positions, accelerations);
+    for (v, a) in velocities.iter_mut().zip(accelerations.iter()) {
+        *v = *v + *a * dt;
+    }
+}
+
+// Velocity Verlet (kick-drift-kick). Positions and velocities are in step
+// after every `step`, at the cost of two acceleration evaluations.
+pub struct VerletSolver<T, S> {
+    pub positions: Vec<T>,
+    pub velocities: Vec<T>,
+    pub time: f64,
+    pub system: S,
+    accelerations: Vec<T>,
+}
+
+impl<T, S> VerletSolver<T, S> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T>,
+    S: System<T> {
+    pub fn from_system(positions: Vec<T>, velocities: Vec<T>, system: S) -> Self {
+        assert_eq!(positions.len(), velocities.len(), "Verlet positions and velocities differ in length");
+        VerletSolver {
+            accelerations: positions.clone(),
+            positions,
+            velocities,
+            time: 0.0,
+            system,
+        }
     }
-}
\ No newline at end of file
+}
+
+impl<T, F> VerletSolver<T, F> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T>,
+    F: FnMut(f64, &[T], &mut [T]) {
+    pub fn new(positions: Vec<T>, velocities: Vec<T>, acceleration_func: F) -> Self {
+        VerletSolver::from_system(positions, velocities, acceleration_func)
+    }
+}
+
+impl<T, S> SecondOrderSolver<T> for VerletSolver<T, S> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T>,
+    S: System<T> {
+    fn positions(&self) -> &[T] {
+        &self.positions
+    }
+
+    fn velocities(&self) -> &[T] {
+        &self.velocities
+    }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn step(&mut self, dt: f32) {
+        kick(&mut self.system, self.time, &self.positions, &mut self.velocities, &mut self.accelerations, dt / 2.0);
+        for (x, v) in self.positions.iter_mut().zip(self.velocities.iter()) {
+            *x = *x + *v * dt;
+        }
+        self.time += dt as f64;
+        kick(&mut self.system, self.time, &self.positions, &mut self.velocities, &mut self.accelerations, dt / 2.0);
+    }
+}
+
+// Leapfrog (drift-kick with staggered velocities). One acceleration
+// evaluation per step, but between steps `velocities` are half a step
+// ahead of `positions`; `synchronize` brings them back in line.
+pub struct LeapfrogSolver<T, S> {
+    pub positions: Vec<T>,
+    pub velocities: Vec<T>,
+    // The time of `positions`.
+    pub time: f64,
+    pub system: S,
+    accelerations: Vec<T>,
+    // The last dt while the velocities are staggered.
+    staggered: Option<f32>,
+}
+
+impl<T, S> LeapfrogSolver<T, S> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T>,
+    S: System<T> {
+    pub fn from_system(positions: Vec<T>, velocities: Vec<T>, system: S) -> Self {
+        assert_eq!(positions.len(), velocities.len(), "Leapfrog positions and velocities differ in length");
+        LeapfrogSolver {
+            accelerations: positions.clone(),
+            positions,
+            velocities,
+            time: 0.0,
+            system,
+            staggered: None,
+        }
+    }
+
+    pub fn is_synchronized(&self) -> bool {
+        self.staggered.is_none()
+    }
+
+    // Moves the velocities back to the same time as the positions, for
+    // reading or changing them. Costs one evaluation, and the next step
+    // starts a new stagger.
+    pub fn synchronize(&mut self) {
+        if let Some(dt) = self.staggered.take() {
+            kick(&mut self.system, self.time, &self.positions, &mut self.velocities, &mut self.accelerations, dt / 2.0);
+        }
+    }
+}
+
+impl<T, F> LeapfrogSolver<T, F> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T>,
+    F: FnMut(f64, &[T], &mut [T]) {
+    pub fn new(positions: Vec<T>, velocities: Vec<T>, acceleration_func: F) -> Self {
+        LeapfrogSolver::from_system(positions, velocities, acceleration_func)
+    }
+}
+
+impl<T, S> SecondOrderSolver<T> for LeapfrogSolver<T, S> where
+    T: Copy + Mul<f32, Output = T> + Add<Output = T>,
+    S: System<T> {
+    fn positions(&self) -> &[T] {
+        &self.positions
+    }
+
+    fn velocities(&self) -> &[T] {
+        &self.velocities
+    }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn step(&mut self, dt: f32) {
+        // From in step, the first kick only covers half of this step. After
+        // that each kick spans the two half steps around the positions.
+        let kick_dt = match self.staggered {
+            Some(last) => (last + dt) / 2.0,
+            None => dt / 2.0,
+        };
+        kick(&mut self.system, self.time, &self.positions, &mut self.velocities, &mut self.accelerations, kick_dt);
+        for (x, v) in self.positions.iter_mut().zip(self.velocities.iter()) {
+            *x = *x + *v * dt;
+        }
+        self.time += dt as f64;
+        self.staggered = Some(dt);
+    }
+}
+
+// Systems the implicit solvers can linearize. The provided `jacobian`
+// estimates it by finite differences; override it when the analytic one is
+// known, which is both cheaper and more accurate.
+pub trait StiffSystem: System<f32> {
+    // Writes `d out[i] / d state[j]` to row i, column j of `out`.
+    fn jacobian(&mut self, t: f64, state: &[f32], out: &mut MatN) {
+        finite_difference_jacobian(self, t, state, out)
+    }
+}
+
+impl<F> StiffSystem for F where
+    F: FnMut(f64, &[f32], &mut [f32]) {}
+
+// Forward differences, one extra evaluation per state element.
+pub fn finite_difference_jacobian<S: System<f32> + ?Sized>(system: &mut S, t: f64, state: &[f32], out: &mut MatN) {
+    let n = state.len();
+    let mut base = vec![0.0; n];
+    let mut shifted = vec![0.0; n];
+    let mut probe = state.to_vec();
+    system.derivatives(t, state, &mut base);
+
+    for j in 0..n {
+        // Scaled to the element so that large values still see a change.
+        let h = f32::EPSILON.sqrt() * state[j].abs().max(1.0);
+        probe[j] = state[j] + h;
+        system.derivatives(t, &probe, &mut shifted);
+        probe[j] = state[j];
+        for i in 0..n {
+            out.set(i, j, (shifted[i] - base[i]) / h);
+        }
+    }
+}
+
+// Backward Euler, `y1 = y0 + dt * f(t + dt, y1)`, solved by Newton iteration.
+// Stable for stiff systems at any dt, at the cost of damping oscillations.
+// With `max_iterations` at 1 it becomes the linearly implicit
+// (semi-implicit) Euler method. Works on flat `f32` state; pack vectors
+// into it component by component.
+pub struct ImplicitEulerSolver<S> {
+    pub state: Vec<f32>,
+    pub time: f64,
+    pub system: S,
+    // Newton stops once no element changes by more than this, relative to
+    // its size as in `Rk45Solver`.
+    pub tolerance: f32,
+    pub max_iterations: u32,
+    // Whether the last step met `tolerance`. A step that doesn't is still
+    // taken, with the last Newton iterate.
+    pub converged: bool,
+    next: Vec<f32>,
+    derivatives: Vec<f32>,
+    jacobian: MatN,
+}
+
+impl<S: StiffSystem> ImplicitEulerSolver<S> {
+    pub fn from_system(initial_state: Vec<f32>, system: S) -> Self {
+        let n = initial_state.len();
+        ImplicitEulerSolver {
+            next: initial_state.clone(),
+            derivatives: initial_state.clone(),
+            state: initial_state,
+            time: 0.0,
+            system,
+            tolerance: 1e-5,
+            max_iterations: 10,
+            converged: true,
+            jacobian: MatN::zero(n, n),
+        }
+    }
+}
+
+impl<F> ImplicitEulerSolver<F> where
+    F: FnMut(f64, &[f32], &mut [f32]) {
+    pub fn new(initial_state: Vec<f32>, derivative_func: F) -> Self {
+        ImplicitEulerSolver::from_system(initial_state, derivative_func)
+    }
+}
+
+impl<S: StiffSystem> OdeSolver<f32> for ImplicitEulerSolver<S> {
+    fn state(&self) -> &[f32] {
+        &self.state
+    }
+
+    fn time(&self) -> f64 {
+        self.time
+    }
+
+    fn step(&mut self, dt: f32) {
+        let t = self.time + dt as f64;
+        let n = self.state.len();
+        self.next.copy_from_slice(&self.state);
+        self.converged = false;
+
+        for _ in 0..self.max_iterations {
+            // Newton on `g(y) = y - y0 - dt * f(t, y)`, whose Jacobian is `I - dt * J`.
+            self.system.derivatives(t, &self.next, &mut self.derivatives);
+            self.system.jacobian(t, &self.next, &mut self.jacobian);
+
+            let mut matrix = MatN::identity(n);
+            for (m, j) in matrix.data.iter_mut().zip(self.jacobian.data.iter()) {
+                *m -= dt * j;
+            }
+            let Some(lu) = matrix.lu() else {
+                break;
+            };
+
+            let mut delta: Vec<f32> = (0..n)
+                .map(|i| self.state[i] + dt * self.derivatives[i] - self.next[i])
+                .collect();
+            lu.solve_in_place(&mut delta);
+
+            let mut converged = true;
+            for (y, d) in self.next.iter_mut().zip(delta.iter()) {
+                *y += d;
+                converged &= d.abs() <= self.tolerance * (1.0 + y.abs());
+            }
+            if converged {
+                self.converged = true;
+                break;
+            }
+        }
+
+        std::mem::swap(&mut self.state, &mut self.next);
+        self.time = t;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // x'' = -x from x = 1, v = 0, so x = cos(t) and v = -sin(t).
+    fn oscillator(_t: f64, state: &[f32], out: &mut [f32]) {
+        out[0] = state[1];
+        out[1] = -state[0];
+    }
+
+    fn decay(_t: f64, state: &[f32], out: &mut [f32]) {
+        out[0] = -state[0];
+    }
+
+    #[test]
+    fn rk4_follows_the_analytic_solutions() {
+        let mut solver = Rk4Solver::new(vec![1.0, 0.0], oscillator);
+        for _ in 0..1000 {
+            solver.step(0.01);
+        }
+        let t = solver.time() as f32;
+        assert!((t - 10.0).abs() < 1e-4);
+        assert!((solver.state[0] - t.cos()).abs() < 1e-4);
+        assert!((solver.state[1] + t.sin()).abs() < 1e-4);
+
+        let mut solver = Rk4Solver::new(vec![1.0], decay);
+        for _ in 0..100 {
+            solver.step(0.05);
+        }
+        assert!((solver.state[0] - (-5.0f32).exp()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rk45_follows_the_analytic_solutions() {
+        let mut solver = Rk45Solver::new(vec![1.0, 0.0], 1e-6, oscillator);
+        for _ in 0..10 {
+            solver.step(1.0);
+        }
+        assert!((solver.state[0] - 10.0f32.cos()).abs() < 1e-4);
+        assert!((solver.state[1] + 10.0f32.sin()).abs() < 1e-4);
+
+        let mut solver = Rk45Solver::new(vec![1.0], 1e-6, decay);
+        solver.step(5.0);
+        assert!((solver.time() - 5.0).abs() < 1e-9);
+        assert!((solver.state[0] - (-5.0f32).exp()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rk45_rejects_steps_over_tolerance() {
+        let tolerance = 1e-5;
+        // Decay that speeds up thirtyfold at t = 1, where the substep size
+        // carried over from the slow part is far too large.
+        let mut solver = Rk45Solver::new(vec![1.0], tolerance, |t: f64, s: &[f32], o: &mut [f32]| {
+            o[0] = if t < 1.0 { -s[0] } else { -30.0 * s[0] };
+        });
+        solver.step(1.0);
+        assert!((solver.state[0] - (-1.0f32).exp()).abs() < tolerance);
+
+        let rejected = solver.rejected_steps;
+        solver.step(0.2);
+        assert!(solver.rejected_steps > rejected);
+        assert!((solver.state[0] - (-7.0f32).exp()).abs() < tolerance);
+    }
+
+    #[test]
+    fn rk45_rejects_overflowing_first_steps() {
+        // Explicit methods are only stable here for substeps below about
+        // 3 / rate, so trying anything near dt would overflow.
+        for rate in [1e5, 1e7] {
+            let mut solver = Rk45Solver::new(vec![1.0], 1e-6, move |_, s: &[f32], o: &mut [f32]| o[0] = -rate * s[0]);
+            solver.min_step = 1e-9;
+            solver.step(0.01);
+            assert!(solver.state[0].is_finite() && solver.state[0].abs() < 1e-5, "{:?}", solver.state);
+            assert!(solver.accepted_steps > 1);
+            assert!((solver.time() - 0.01).abs() < 1e-9);
+        }
+
+        let mut solver = Rk45Solver::new(vec![1.0], 1e-6, |_, s: &[f32], o: &mut [f32]| o[0] = -1e5 * s[0]);
+        solver.step(1.0);
+        assert!(solver.state[0].is_finite());
+        assert!(solver.rejected_steps > 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rk45_refuses_infinite_steps() {
+        let mut solver = Rk45Solver::new(vec![1.0], 1e-5, decay);
+        solver.step(f32::INFINITY);
+    }
+
+    // Two unit masses with G = 1, a unit distance apart, circling their
+    // center of mass with period 2 pi / sqrt(2).
+    fn gravity(_t: f64, positions: &[Vec2], out: &mut [Vec2]) {
+        let offset = positions[1] - positions[0];
+        let pull = offset / offset.length().powi(3);
+        out[0] = pull;
+        out[1] = -pull;
+    }
+
+    fn orbit() -> (Vec<Vec2>, Vec<Vec2>) {
+        let speed = 0.5f32.sqrt();
+        (
+            vec![Vec2::new([-0.5, 0.0]), Vec2::new([0.5, 0.0])],
+            vec![Vec2::new([0.0, -speed]), Vec2::new([0.0, speed])],
+        )
+    }
+
+    fn energy(positions: &[Vec2], velocities: &[Vec2]) -> f32 {
+        let kinetic: f32 = velocities.iter().map(|v| v.length_squared() / 2.0).sum();
+        kinetic - 1.0 / (positions[1] - positions[0]).length()
+    }
+
+    const PERIODS: usize = 20;
+    const STEPS_PER_PERIOD: usize = 200;
+
+    // Relative energy drift after each orbit, from `orbit` returning the
+    // energy after one more period.
+    fn drifts(mut orbit: impl FnMut() -> f32) -> Vec<f32> {
+        (0..PERIODS).map(|_| ((orbit() + 0.5) / 0.5).abs()).collect()
+    }
+
+    #[test]
+    fn symplectic_solvers_conserve_orbit_energy() {
+        let dt = std::f32::consts::TAU / 2.0f32.sqrt() / STEPS_PER_PERIOD as f32;
+        let (positions, velocities) = orbit();
+        assert!((energy(&positions, &velocities) + 0.5).abs() < 1e-6);
+
+        let mut verlet = VerletSolver::new(positions.clone(), velocities.clone(), gravity);
+        let verlet_drifts = drifts(|| {
+            (0..STEPS_PER_PERIOD).for_each(|_| verlet.step(dt));
+            energy(&verlet.positions, &verlet.velocities)
+        });
+
+        let mut leapfrog = LeapfrogSolver::new(positions.clone(), velocities.clone(), gravity);
+        let leapfrog_drifts = drifts(|| {
+            (0..STEPS_PER_PERIOD).for_each(|_| leapfrog.step(dt));
+            leapfrog.synchronize();
+            energy(&leapfrog.positions, &leapfrog.velocities)
+        });
+
+        let mut state = positions.clone();
+        state.extend(velocities.iter());
+        let mut euler = EulerSolver::new(state, |t, state: &[Vec2], out: &mut [Vec2]| {
+            out[..2].copy_from_slice(&state[2..]);
+            gravity(t, &state[..2], &mut out[2..]);
+        });
+        let euler_drifts = drifts(|| {
+            (0..STEPS_PER_PERIOD).for_each(|_| euler.step(dt));
+            energy(&euler.state[..2], &euler.state[2..])
+        });
+
+        for drifts in [&verlet_drifts, &leapfrog_drifts] {
+            assert!(drifts.iter().all(|&drift| drift < 1e-3), "{:?}", drifts);
+            // Oscillating, not growing.
+            assert!(drifts[PERIODS - 1] < 2.0 * drifts[0] + 1e-5, "{:?}", drifts);
+        }
+
+        assert!(euler_drifts.windows(2).all(|pair| pair[1] > pair[0]), "{:?}", euler_drifts);
+        assert!(euler_drifts[PERIODS - 1] > 100.0 * verlet_drifts[PERIODS - 1].max(leapfrog_drifts[PERIODS - 1]));
+    }
+
+    #[test]
+    fn implicit_euler_stays_stable_on_stiff_decay() {
+        // Explicit Euler multiplies by 1 - 1000 * 0.1 = -99 every step.
+        let stiff = |_t: f64, state: &[f32], out: &mut [f32]| out[0] = -1000.0 * state[0];
+
+        let mut implicit = ImplicitEulerSolver::new(vec![1.0], stiff);
+        let mut explicit = EulerSolver::new(vec![1.0], stiff);
+        for _ in 0..10 {
+            implicit.step(0.1);
+            explicit.step(0.1);
+            assert!(implicit.converged);
+            assert!(implicit.state[0] >= 0.0 && implicit.state[0] <= 1.0);
+        }
+
+        // Exactly 1 / (1 + 1000 * 0.1) per step.
+        assert!(implicit.state[0] < 1e-19);
+        assert!(explicit.state[0].abs() > 1e19);
+    }
+}
@@ -0,0 +1,469 @@
+// Wavefront OBJ models with their MTL materials. Faces are triangulated and
+// split into one mesh per object, group and material, in the
+// `VertexLayout::position_uv_normal` layout. Texture coordinates are flipped
+// to the engine's top down v, and meshes with faces lacking normals get
+// smooth ones computed for the whole mesh.
+
+use std::collections::HashMap;
+
+use crate::{linalg::Vec3, vertex::VertexLayout, ContextType, Mesh, NormalMode};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Material {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub emissive: [f32; 3],
+    pub shininess: f32,
+    pub opacity: f32,
+    // Asset names. `load` resolves them against the directory of the model,
+    // `parse_mtl` keeps them as written.
+    pub diffuse_texture: Option<String>,
+    pub normal_texture: Option<String>,
+}
+
+impl Material {
+    // The defaults from the MTL specification.
+    pub fn new(name: &str) -> Self {
+        Material {
+            name: name.to_string(),
+            ambient: [0.2; 3],
+            diffuse: [0.8; 3],
+            specular: [1.0; 3],
+            emissive: [0.0; 3],
+            shininess: 0.0,
+            opacity: 1.0,
+            diffuse_texture: None,
+            normal_texture: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjMesh {
+    pub object: String,
+    pub group: String,
+    pub material: Option<String>,
+    pub mesh: Mesh,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjModel {
+    pub meshes: Vec<ObjMesh>,
+    // The mtllib names as written.
+    pub material_libraries: Vec<String>,
+    pub materials: Vec<Material>,
+}
+
+impl ObjModel {
+    pub fn material(&self, name: &str) -> Option<&Material> {
+        self.materials.iter().find(|material| material.name == name)
+    }
+}
+
+// Loads a model and its material libraries through `ContextType::load_asset`.
+// Names in the model are relative to its own directory.
+pub async fn load<Context: ContextType>(ctx: &Context, name: &str) -> Result<ObjModel, String> {
+    let bytes = ctx.load_asset(name).await?;
+    let mut model = parse_obj(&String::from_utf8_lossy(&bytes))
+        .map_err(|err| format!("Failed to parse {}: {}", name, err))?;
+
+    for library in &model.material_libraries {
+        let library = sibling(name, library);
+        let bytes = ctx.load_asset(&library).await?;
+        let materials = parse_mtl(&String::from_utf8_lossy(&bytes))
+            .map_err(|err| format!("Failed to parse {}: {}", library, err))?;
+
+        for mut material in materials {
+            material.diffuse_texture = material.diffuse_texture.map(|texture| sibling(&library, &texture));
+            material.normal_texture = material.normal_texture.map(|texture| sibling(&library, &texture));
+            model.materials.push(material);
+        }
+    }
+
+    Ok(model)
+}
+
+fn sibling(name: &str, other: &str) -> String {
+    match name.rfind('/') {
+        Some(i) => format!("{}{}", &name[..=i], other),
+        None => other.to_string(),
+    }
+}
+
+// Statements with the comments removed, numbered from 1.
+fn statements(text: &str) -> impl Iterator<Item = (usize, &str, Vec<&str>)> {
+    text.lines().enumerate().filter_map(|(i, line)| {
+        let line = line.split('#').next().unwrap_or("");
+        let mut tokens = line.split_whitespace();
+        let keyword = tokens.next()?;
+        Some((i + 1, keyword, tokens.collect()))
+    })
+}
+
+fn floats<const N: usize>(line: usize, args: &[&str], required: usize) -> Result<[f32; N], String> {
+    if args.len() < required {
+        return Err(format!("Line {}: expected {} numbers", line, required));
+    }
+
+    let mut values = [0.0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg.parse().map_err(|_| format!("Line {}: invalid number {}", line, arg))?;
+    }
+    Ok(values)
+}
+
+// OBJ indices count from 1, or back from the end when negative.
+fn index(line: usize, token: &str, count: usize) -> Result<usize, String> {
+    let i: i64 = token.parse().map_err(|_| format!("Line {}: invalid index {}", line, token))?;
+    let resolved = if i > 0 { i - 1 } else { count as i64 + i };
+    if i == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err(format!("Line {}: index {} out of range", line, i));
+    }
+    Ok(resolved as usize)
+}
+
+// A mesh being filled with the faces of one object, group and material.
+struct Part {
+    object: String,
+    group: String,
+    material: Option<String>,
+    vertices: Vec<f32>,
+    indices: Vec<u32>,
+    // Vertex numbers of the (position, uv, normal) combinations seen so far.
+    corners: HashMap<(usize, Option<usize>, Option<usize>), u32>,
+    missing_normals: bool,
+}
+
+impl Part {
+    fn new(object: &str, group: &str, material: Option<&str>) -> Self {
+        Part {
+            object: object.to_string(),
+            group: group.to_string(),
+            material: material.map(str::to_string),
+            vertices: vec![],
+            indices: vec![],
+            corners: HashMap::new(),
+            missing_normals: false,
+        }
+    }
+
+    // Continues with `next`, keeping the faces so far as a mesh.
+    fn replace(&mut self, next: Part, meshes: &mut Vec<ObjMesh>) {
+        std::mem::replace(self, next).finish(meshes);
+    }
+
+    fn finish(self, meshes: &mut Vec<ObjMesh>) {
+        if self.indices.is_empty() {
+            return;
+        }
+
+        let mut mesh = Mesh::with_layout(self.vertices, self.indices, VertexLayout::position_uv_normal());
+        if self.missing_normals {
+            mesh.compute_normals(NormalMode::Smooth);
+        }
+        meshes.push(ObjMesh { object: self.object, group: self.group, material: self.material, mesh });
+    }
+}
+
+pub fn parse_obj(text: &str) -> Result<ObjModel, String> {
+    let mut positions: Vec<Vec3> = vec![];
+    let mut uvs: Vec<[f32; 2]> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+
+    let mut model = ObjModel::default();
+    let mut part = Part::new("default", "default", None);
+
+    for (line, keyword, args) in statements(text) {
+        match keyword {
+            "v" => positions.push(Vec3::new(floats(line, &args, 3)?)),
+            "vt" => uvs.push(floats(line, &args, 1)?),
+            "vn" => normals.push(Vec3::new(floats(line, &args, 3)?).normalize()),
+            "f" => {
+                if args.len() < 3 {
+                    return Err(format!("Line {}: faces need at least 3 vertices", line));
+                }
+
+                let mut corners = vec![];
+                for arg in &args {
+                    let mut parts = arg.split('/');
+                    let position = index(line, parts.next().unwrap_or(""), positions.len())?;
+                    let uv = match parts.next() {
+                        Some(uv) if !uv.is_empty() => Some(index(line, uv, uvs.len())?),
+                        _ => None,
+                    };
+                    let normal = match parts.next() {
+                        Some(normal) if !normal.is_empty() => Some(index(line, normal, normals.len())?),
+                        _ => None,
+                    };
+                    corners.push((position, uv, normal));
+                }
+
+                let polygon: Vec<Vec3> = corners.iter().map(|c| positions[c.0]).collect();
+                for triangle in triangulate(&polygon) {
+                    for corner in triangle.map(|i| corners[i]) {
+                        let next = part.corners.len() as u32;
+                        let vertex = *part.corners.entry(corner).or_insert(next);
+                        if vertex == next {
+                            let (position, uv, normal) = corner;
+                            let uv = uv.map_or([0.0, 0.0], |uv| [uvs[uv][0], 1.0 - uvs[uv][1]]);
+                            part.missing_normals |= normal.is_none();
+                            part.vertices.extend_from_slice(&positions[position].data);
+                            part.vertices.extend_from_slice(&uv);
+                            part.vertices.extend_from_slice(&normal.map_or(Vec3::ZERO, |n| normals[n]).data);
+                        }
+                        part.indices.push(vertex);
+                    }
+                }
+            }
+            "o" => {
+                let next = Part::new(&args.join(" "), "default", part.material.as_deref());
+                part.replace(next, &mut model.meshes);
+            }
+            "g" => {
+                let group = if args.is_empty() { "default".to_string() } else { args.join(" ") };
+                let next = Part::new(&part.object, &group, part.material.as_deref());
+                part.replace(next, &mut model.meshes);
+            }
+            "usemtl" => {
+                let next = Part::new(&part.object, &part.group, Some(&args.join(" ")));
+                part.replace(next, &mut model.meshes);
+            }
+            "mtllib" => model.material_libraries.push(args.join(" ")),
+            // Smoothing groups, lines, points and anything else don't apply.
+            _ => {}
+        }
+    }
+
+    part.finish(&mut model.meshes);
+    Ok(model)
+}
+
+pub fn parse_mtl(text: &str) -> Result<Vec<Material>, String> {
+    let mut materials: Vec<Material> = vec![];
+
+    for (line, keyword, args) in statements(text) {
+        if keyword == "newmtl" {
+            materials.push(Material::new(&args.join(" ")));
+            continue;
+        }
+
+        let Some(material) = materials.last_mut() else {
+            return Err(format!("Line {}: {} before newmtl", line, keyword));
+        };
+        match keyword {
+            "Ka" => material.ambient = floats(line, &args, 3)?,
+            "Kd" => material.diffuse = floats(line, &args, 3)?,
+            "Ks" => material.specular = floats(line, &args, 3)?,
+            "Ke" => material.emissive = floats(line, &args, 3)?,
+            "Ns" => material.shininess = floats::<1>(line, &args, 1)?[0],
+            "d" => material.opacity = floats::<1>(line, &args, 1)?[0],
+            "Tr" => material.opacity = 1.0 - floats::<1>(line, &args, 1)?[0],
+            // Texture options come before the file name.
+            "map_Kd" => material.diffuse_texture = args.last().map(|name| name.to_string()),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.normal_texture = args.last().map(|name| name.to_string()),
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+// Ear clipping in the plane of the polygon, so concave faces work. Whatever
+// is left when no ear can be found, e.g. for self intersecting polygons, is
+// fanned out.
+fn triangulate(polygon: &[Vec3]) -> Vec<[usize; 3]> {
+    let count = polygon.len();
+    if count == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Newell's method, which works for concave polygons too.
+    let mut normal = Vec3::ZERO;
+    for i in 0..count {
+        let (a, b) = (polygon[i], polygon[(i + 1) % count]);
+        normal += Vec3::new([
+            (a[1] - b[1]) * (a[2] + b[2]),
+            (a[2] - b[2]) * (a[0] + b[0]),
+            (a[0] - b[0]) * (a[1] + b[1]),
+        ]);
+    }
+    let left_of = |a: Vec3, b: Vec3, p: Vec3| (b - a).cross(&(p - a)).dot(&normal) >= 0.0;
+
+    let mut remaining: Vec<usize> = (0..count).collect();
+    let mut triangles = vec![];
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let corners = |i: usize| [remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]];
+        let ear = (0..n).find(|&i| {
+            let [a, b, c] = corners(i).map(|k| polygon[k]);
+            let convex = (b - a).cross(&(c - b)).dot(&normal) > 0.0;
+            convex && !remaining.iter()
+                .filter(|k| !corners(i).contains(k))
+                .any(|&k| left_of(a, b, polygon[k]) && left_of(b, c, polygon[k]) && left_of(c, a, polygon[k]))
+        });
+
+        match ear {
+            Some(i) => {
+                triangles.push(corners(i));
+                remaining.remove(i);
+            }
+            None => break,
+        }
+    }
+
+    for i in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[i], remaining[i + 1]]);
+    }
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(mesh: &Mesh) -> Vec<Vec3> {
+        mesh.vertices.chunks_exact(mesh.layout.vertex_size())
+            .map(|vertex| {
+                let [x, y, z, _] = mesh.layout.read(vertex, "v_position").unwrap();
+                Vec3::new([x, y, z])
+            })
+            .collect()
+    }
+
+    fn area(mesh: &Mesh) -> f32 {
+        let positions = positions(mesh);
+        mesh.indices.chunks_exact(3)
+            .map(|t| {
+                let [a, b, c] = [t[0], t[1], t[2]].map(|i| positions[i as usize]);
+                (b - a).cross(&(c - a)).length() / 2.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_end() {
+        let model = parse_obj("
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vn 0 0 2
+            f -3/-3/-1 -2/-2/-1 -1/-1/-1
+        ").unwrap();
+
+        let mesh = &model.meshes[0].mesh;
+        assert_eq!(positions(mesh), vec![Vec3::new([0.0, 0.0, 0.0]), Vec3::new([1.0, 0.0, 0.0]), Vec3::new([1.0, 1.0, 0.0])]);
+        // v is flipped to run top down, and normals are normalized.
+        assert_eq!(mesh.layout.read(&mesh.vertices[16..24], "v_uv").unwrap()[..2], [1.0, 0.0]);
+        assert_eq!(mesh.layout.read(&mesh.vertices[..8], "v_normal").unwrap()[..3], [0.0, 0.0, 1.0]);
+
+        assert!(parse_obj("v 0 0 0\nf -2 1 1").is_err());
+        assert!(parse_obj("v 0 0 0\nf 0 1 1").is_err());
+    }
+
+    #[test]
+    fn concave_faces_are_ear_clipped() {
+        // An L of three unit squares. A fan from the first vertex would
+        // cover the missing square with a backwards triangle.
+        let model = parse_obj("
+            v 2 0 0
+            v 2 1 0
+            v 1 1 0
+            v 1 2 0
+            v 0 2 0
+            v 0 0 0
+            f 1 2 3 4 5 6
+        ").unwrap();
+
+        let mesh = &model.meshes[0].mesh;
+        assert_eq!(mesh.indices.len(), 3 * 4);
+        assert!((area(mesh) - 3.0).abs() < 1e-5);
+
+        // Every triangle keeps the polygon's winding.
+        let positions = positions(mesh);
+        for t in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [t[0], t[1], t[2]].map(|i| positions[i as usize]);
+            assert!((b - a).cross(&(c - a))[2] > 0.0);
+        }
+    }
+
+    #[test]
+    fn faces_are_split_by_object_group_and_material() {
+        let model = parse_obj("
+            mtllib scene.mtl
+            v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            f 1 2 3
+            o box
+            usemtl wood
+            f 1 2 3
+            g lid
+            f 1 2 3
+            f 3 2 1
+            usemtl metal
+            f 1 2 3
+            o empty
+            o ball
+            f 1 2 3
+        ").unwrap();
+
+        let parts: Vec<(&str, &str, Option<&str>, usize)> = model.meshes.iter()
+            .map(|m| (m.object.as_str(), m.group.as_str(), m.material.as_deref(), m.mesh.indices.len() / 3))
+            .collect();
+        assert_eq!(parts, vec![
+            ("default", "default", None, 1),
+            ("box", "default", Some("wood"), 1),
+            ("box", "lid", Some("wood"), 2),
+            ("box", "lid", Some("metal"), 1),
+            ("ball", "default", Some("metal"), 1),
+        ]);
+        assert_eq!(model.material_libraries, vec!["scene.mtl"]);
+
+        // Without normals in the file, smooth ones are computed.
+        let mesh = &model.meshes[0].mesh;
+        assert_eq!(mesh.layout.read(&mesh.vertices[..8], "v_normal").unwrap()[..3], [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn parses_materials() {
+        let materials = parse_mtl("
+            # Exported materials
+            newmtl wood
+            Kd 0.5 0.25 0.125
+            Ks 0 0 0
+            Ns 20
+            d 0.5
+            map_Kd -s 2 2 1 textures/wood.png
+            map_Bump wood_normal.png
+
+            newmtl glass
+            Tr 0.75
+        ").unwrap();
+
+        assert_eq!(materials.len(), 2);
+        let wood = &materials[0];
+        assert_eq!(wood.name, "wood");
+        assert_eq!(wood.diffuse, [0.5, 0.25, 0.125]);
+        assert_eq!(wood.specular, [0.0; 3]);
+        assert_eq!(wood.ambient, Material::new("").ambient);
+        assert_eq!(wood.shininess, 20.0);
+        assert_eq!(wood.opacity, 0.5);
+        assert_eq!(wood.diffuse_texture.as_deref(), Some("textures/wood.png"));
+        assert_eq!(wood.normal_texture.as_deref(), Some("wood_normal.png"));
+        assert_eq!(materials[1].opacity, 0.25);
+
+        assert!(parse_mtl("Kd 1 1 1").is_err());
+        assert!(parse_mtl("newmtl a\nKd 1 x 1").unwrap_err().starts_with("Line 2"));
+    }
+
+    #[test]
+    fn material_names_resolve_next_to_the_model() {
+        assert_eq!(sibling("models/ship.obj", "ship.mtl"), "models/ship.mtl");
+        assert_eq!(sibling("ship.obj", "ship.mtl"), "ship.mtl");
+    }
+}
@@ -0,0 +1,568 @@
+// Parametric meshes with positions, uvs and normals in the
+// `VertexLayout::position_uv_normal` layout. Solids are centered on the
+// origin with Y up, and their triangles wind counter-clockwise seen from
+// outside, the front faces kept by CULL_FACE. Around the Y axis u runs left
+// to right seen from outside, and v runs from the top down like the rows of
+// a texture image.
+
+use std::{collections::HashMap, f32::consts::PI};
+
+use crate::{linalg::Vec3, vertex::VertexLayout, Mesh};
+
+const VERTEX_SIZE: usize = 8;
+
+// A point of a lathe profile in the (distance from the Y axis, y) plane.
+struct ProfilePoint {
+    radius: f32,
+    y: f32,
+    // Unit normal in the same plane.
+    normal: [f32; 2],
+    v: f32,
+}
+
+impl ProfilePoint {
+    fn new(radius: f32, y: f32, normal: [f32; 2], v: f32) -> Self {
+        ProfilePoint { radius, y, normal, v }
+    }
+}
+
+// Angle `slice / slices` of the way around the Y axis, as the direction
+// (x, z). Increasing angles run counter-clockwise seen from above. The
+// last slice comes back to exactly the first direction.
+fn around(slice: u32, slices: u32) -> (f32, f32) {
+    let (sin, cos) = (2.0 * PI * (slice % slices) as f32 / slices as f32).sin_cos();
+    (cos, -sin)
+}
+
+#[derive(Default)]
+struct Builder {
+    vertices: Vec<f32>,
+    indices: Vec<u32>,
+}
+
+impl Builder {
+    fn next_index(&self) -> u32 {
+        (self.vertices.len() / VERTEX_SIZE) as u32
+    }
+
+    fn vertex(&mut self, position: Vec3, uv: [f32; 2], normal: Vec3) -> u32 {
+        let index = self.next_index();
+        self.vertices.extend_from_slice(&position.data);
+        self.vertices.extend_from_slice(&uv);
+        self.vertices.extend_from_slice(&normal.data);
+        index
+    }
+
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.indices.extend_from_slice(&[a, b, c]);
+    }
+
+    // Corners in counter-clockwise order.
+    fn quad(&mut self, a: u32, b: u32, c: u32, d: u32) {
+        self.triangle(a, b, c);
+        self.triangle(a, c, d);
+    }
+
+    // Sweeps a profile, given from the top down, once around the Y axis.
+    // The seam gets a second column of vertices for u = 1.
+    fn lathe(&mut self, profile: &[ProfilePoint], slices: u32) {
+        let first = self.next_index();
+        for point in profile {
+            for j in 0..slices+1 {
+                let (x, z) = around(j, slices);
+                self.vertex(
+                    Vec3::new([point.radius * x, point.y, point.radius * z]),
+                    [j as f32 / slices as f32, point.v],
+                    Vec3::new([point.normal[0] * x, point.normal[1], point.normal[0] * z]),
+                );
+            }
+        }
+
+        // Points on the axis would make half of their quads degenerate.
+        let columns = slices + 1;
+        for (i, rows) in profile.windows(2).enumerate() {
+            for j in 0..slices {
+                let a = first + i as u32 * columns + j;
+                let (b, c, d) = (a + 1, a + columns + 1, a + columns);
+                if rows[1].radius > 0.0 {
+                    self.triangle(a, d, c);
+                }
+                if rows[0].radius > 0.0 {
+                    self.triangle(a, c, b);
+                }
+            }
+        }
+    }
+
+    // A disc facing up or down, with uvs mapped straight down from above.
+    fn cap(&mut self, radius: f32, y: f32, slices: u32, up: bool) {
+        let facing = if up { 1.0 } else { -1.0 };
+        let normal = Vec3::new([0.0, facing, 0.0]);
+
+        let center = self.vertex(Vec3::new([0.0, y, 0.0]), [0.5, 0.5], normal);
+        for j in 0..slices {
+            let (x, z) = around(j, slices);
+            self.vertex(Vec3::new([radius * x, y, radius * z]), [0.5 + 0.5 * x, 0.5 + 0.5 * z * facing], normal);
+        }
+
+        for j in 0..slices {
+            let a = center + 1 + j;
+            let b = center + 1 + (j + 1) % slices;
+            if up {
+                self.triangle(center, a, b);
+            } else {
+                self.triangle(center, b, a);
+            }
+        }
+    }
+
+    fn build(self) -> Mesh {
+        Mesh::with_layout(self.vertices, self.indices, VertexLayout::position_uv_normal())
+    }
+}
+
+impl Mesh {
+    // A uv sphere. `rings` divide it from pole to pole and `slices` around
+    // the Y axis, with equirectangular uvs so a world map wraps around it
+    // the right way up.
+    pub fn sphere(radius: f32, rings: u32, slices: u32) -> Option<Self> {
+        if rings < 3 || slices < 3 || radius <= 0.0 {
+            return None;
+        }
+
+        let profile: Vec<ProfilePoint> = (0..rings+1)
+            .map(|k| {
+                let v = k as f32 / rings as f32;
+                let (sin, cos) = (PI / 2.0 - PI * v).sin_cos();
+                // Exactly on the axis at the poles.
+                let cos = if k == 0 || k == rings { 0.0 } else { cos };
+                ProfilePoint::new(radius * cos, radius * sin, [cos, sin], v)
+            })
+            .collect();
+
+        let mut builder = Builder::default();
+        builder.lathe(&profile, slices);
+        Some(builder.build())
+    }
+
+    // Each face maps the whole texture.
+    pub fn cube(size: f32) -> Option<Self> {
+        if size <= 0.0 {
+            return None;
+        }
+
+        let half = size / 2.0;
+        let x = Vec3::new([1.0, 0.0, 0.0]);
+        let y = Vec3::new([0.0, 1.0, 0.0]);
+        let z = Vec3::new([0.0, 0.0, 1.0]);
+        // Normal, then the face's right and up directions.
+        let faces = [(x, -z, y), (-x, z, y), (y, x, -z), (-y, x, z), (z, x, y), (-z, -x, y)];
+
+        let mut builder = Builder::default();
+        for (normal, right, up) in faces {
+            let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].map(|(s, t)| {
+                let position = (normal + right * s + up * t) * half;
+                builder.vertex(position, [(s + 1.0) / 2.0, (1.0 - t) / 2.0], normal)
+            });
+            builder.quad(corners[0], corners[1], corners[2], corners[3]);
+        }
+
+        Some(builder.build())
+    }
+
+    // A grid in the XZ plane facing up, with v running towards +Z.
+    pub fn plane(width: f32, depth: f32, columns: u32, rows: u32) -> Option<Self> {
+        if width <= 0.0 || depth <= 0.0 || columns < 1 || rows < 1 {
+            return None;
+        }
+
+        let mut builder = Builder::default();
+        let normal = Vec3::new([0.0, 1.0, 0.0]);
+        for j in 0..rows+1 {
+            for i in 0..columns+1 {
+                let u = i as f32 / columns as f32;
+                let v = j as f32 / rows as f32;
+                builder.vertex(Vec3::new([(u - 0.5) * width, 0.0, (v - 0.5) * depth]), [u, v], normal);
+            }
+        }
+
+        let index = |i: u32, j: u32| j * (columns + 1) + i;
+        for j in 0..rows {
+            for i in 0..columns {
+                builder.quad(index(i, j), index(i, j + 1), index(i + 1, j + 1), index(i + 1, j));
+            }
+        }
+
+        Some(builder.build())
+    }
+
+    // Capped, with the side mapping the whole texture.
+    pub fn cylinder(radius: f32, height: f32, slices: u32) -> Option<Self> {
+        if radius <= 0.0 || height <= 0.0 || slices < 3 {
+            return None;
+        }
+
+        let half = height / 2.0;
+        let mut builder = Builder::default();
+        builder.lathe(&[
+            ProfilePoint::new(radius, half, [1.0, 0.0], 0.0),
+            ProfilePoint::new(radius, -half, [1.0, 0.0], 1.0),
+        ], slices);
+        builder.cap(radius, half, slices, true);
+        builder.cap(radius, -half, slices, false);
+
+        Some(builder.build())
+    }
+
+    // Apex up, with a capped base.
+    pub fn cone(radius: f32, height: f32, slices: u32) -> Option<Self> {
+        if radius <= 0.0 || height <= 0.0 || slices < 3 {
+            return None;
+        }
+
+        let half = height / 2.0;
+        let slope = (radius * radius + height * height).sqrt();
+        let normal = [height / slope, radius / slope];
+
+        let mut builder = Builder::default();
+        builder.lathe(&[
+            ProfilePoint::new(0.0, half, normal, 0.0),
+            ProfilePoint::new(radius, -half, normal, 1.0),
+        ], slices);
+        builder.cap(radius, -half, slices, false);
+
+        Some(builder.build())
+    }
+
+    // Lying in the XZ plane. `rings` go around the tube, v starts on its
+    // outer equator.
+    pub fn torus(major_radius: f32, minor_radius: f32, rings: u32, slices: u32) -> Option<Self> {
+        if minor_radius <= 0.0 || major_radius <= minor_radius || rings < 3 || slices < 3 {
+            return None;
+        }
+
+        let profile: Vec<ProfilePoint> = (0..rings+1)
+            .map(|k| {
+                let v = k as f32 / rings as f32;
+                let (sin, cos) = (-2.0 * PI * (k % rings) as f32 / rings as f32).sin_cos();
+                ProfilePoint::new(major_radius + minor_radius * cos, minor_radius * sin, [cos, sin], v)
+            })
+            .collect();
+
+        let mut builder = Builder::default();
+        builder.lathe(&profile, slices);
+        Some(builder.build())
+    }
+
+    // A cylinder of `height` between two hemispheres, so the total height is
+    // `height + 2 * radius`. `rings` is per hemisphere. v is proportional to
+    // the distance along the surface from the top.
+    pub fn capsule(radius: f32, height: f32, rings: u32, slices: u32) -> Option<Self> {
+        if radius <= 0.0 || height < 0.0 || rings < 1 || slices < 3 {
+            return None;
+        }
+
+        let half = height / 2.0;
+        let quarter = PI / 2.0 * radius;
+        let length = 2.0 * quarter + height;
+
+        let mut profile = vec![];
+        for k in 0..rings+1 {
+            let t = k as f32 / rings as f32;
+            let (sin, cos) = (PI / 2.0 * (1.0 - t)).sin_cos();
+            profile.push(ProfilePoint::new(radius * cos, half + radius * sin, [cos, sin], t * quarter / length));
+        }
+        // Without a cylinder the hemispheres share their equator.
+        let first = if height > 0.0 { 0 } else { 1 };
+        for k in first..rings+1 {
+            let t = k as f32 / rings as f32;
+            let (sin, cos) = (-PI / 2.0 * t).sin_cos();
+            profile.push(ProfilePoint::new(radius * cos, -half + radius * sin, [cos, sin], (quarter + height + t * quarter) / length));
+        }
+
+        let mut builder = Builder::default();
+        builder.lathe(&profile, slices);
+        Some(builder.build())
+    }
+
+    // A subdivided icosahedron, with more even triangles than `Mesh::sphere`.
+    // Each subdivision quadruples the 20 faces. Uvs are equirectangular like
+    // the sphere's, with vertices split along the seam and at the poles.
+    pub fn icosphere(radius: f32, subdivisions: u32) -> Option<Self> {
+        if radius <= 0.0 {
+            return None;
+        }
+
+        let t = (1.0 + 5.0_f32.sqrt()) / 2.0;
+        let mut positions: Vec<Vec3> = [
+            [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+            [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+            [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+        ].map(|p| Vec3::new(p).normalize()).to_vec();
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            // Edges are shared, so each midpoint is made once.
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    positions.push((positions[a as usize] + positions[b as usize]).normalize());
+                    positions.len() as u32 - 1
+                })
+            };
+
+            triangles = triangles.iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        let mut builder = Builder::default();
+        let mut split: HashMap<(u32, u32), u32> = HashMap::new();
+        for triangle in triangles {
+            let mut uvs = triangle.map(|i| {
+                let p = positions[i as usize];
+                [(-p[2]).atan2(p[0]).rem_euclid(2.0 * PI) / (2.0 * PI), p[1].clamp(-1.0, 1.0).acos() / PI]
+            });
+
+            // Triangles across the seam continue past u = 1 instead of
+            // wrapping back to 0.
+            let max_u = uvs.iter().map(|uv| uv[0]).fold(0.0, f32::max);
+            if max_u - uvs.iter().map(|uv| uv[0]).fold(1.0, f32::min) > 0.5 {
+                uvs.iter_mut().filter(|uv| uv[0] < 0.5).for_each(|uv| uv[0] += 1.0);
+            }
+            // u is undefined at a pole, so it takes the middle of the other two.
+            for k in 0..3 {
+                if positions[triangle[k] as usize][1].abs() > 1.0 - 1e-6 {
+                    uvs[k][0] = (uvs[(k + 1) % 3][0] + uvs[(k + 2) % 3][0]) / 2.0;
+                }
+            }
+
+            let corners = [0, 1, 2].map(|k| {
+                let i = triangle[k];
+                *split.entry((i, uvs[k][0].to_bits())).or_insert_with(|| {
+                    let normal = positions[i as usize];
+                    builder.vertex(normal * radius, uvs[k], normal)
+                })
+            });
+            builder.triangle(corners[0], corners[1], corners[2]);
+        }
+
+        Some(builder.build())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{vertex::AttributeType, NormalMode};
+
+    fn attribute(mesh: &Mesh, name: &str) -> Vec<Vec3> {
+        mesh.vertices.chunks_exact(mesh.layout.vertex_size())
+            .map(|vertex| {
+                let [x, y, z, _] = mesh.layout.read(vertex, name).unwrap();
+                Vec3::new([x, y, z])
+            })
+            .collect()
+    }
+
+    fn triangles(mesh: &Mesh) -> impl Iterator<Item = [usize; 3]> + '_ {
+        mesh.indices.chunks_exact(3).map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+    }
+
+    // Vertex numbers with the vertices that only differ in uv or normal
+    // merged, and the number of distinct positions.
+    fn welded(mesh: &Mesh) -> (Vec<usize>, usize) {
+        let mut ids: HashMap<[i32; 3], usize> = HashMap::new();
+        let welded = attribute(mesh, "v_position").iter()
+            .map(|p| {
+                let key = p.data.map(|c| (c * 1e4).round() as i32);
+                let next = ids.len();
+                *ids.entry(key).or_insert(next)
+            })
+            .collect();
+        (welded, ids.len())
+    }
+
+    // Every edge is used once in each direction.
+    fn is_closed(mesh: &Mesh) -> bool {
+        let (welded, _) = welded(mesh);
+        let mut balance: HashMap<(usize, usize), i32> = HashMap::new();
+        for triangle in triangles(mesh) {
+            let [a, b, c] = triangle.map(|i| welded[i]);
+            assert!(a != b && b != c && c != a, "Degenerate triangle {:?}", triangle);
+            for (from, to) in [(a, b), (b, c), (c, a)] {
+                *balance.entry((from.min(to), from.max(to))).or_default() += if from < to { 1 } else { -1 };
+            }
+        }
+        balance.values().all(|&count| count == 0)
+    }
+
+    // Each triangle winds counter-clockwise around its corners' normals.
+    fn assert_wound_outward(mesh: &Mesh) {
+        let positions = attribute(mesh, "v_position");
+        let normals = attribute(mesh, "v_normal");
+        assert!(normals.iter().all(|n| (n.length() - 1.0).abs() < 1e-4));
+
+        for triangle in triangles(mesh) {
+            let [a, b, c] = triangle.map(|i| positions[i]);
+            let face = (b - a).cross(&(c - a));
+            let vertex_normals = triangle.iter().fold(Vec3::ZERO, |sum, &i| sum + normals[i]);
+            assert!(face.dot(&vertex_normals) > 0.0, "Triangle {:?} faces inward", triangle);
+        }
+    }
+
+    fn check_solid(mesh: Mesh, vertices: usize, indices: usize) {
+        assert_eq!(mesh.vertex_count(), vertices);
+        assert_eq!(mesh.indices.len(), indices);
+        assert!(mesh.indices.iter().all(|&i| (i as usize) < vertices));
+        assert!(is_closed(&mesh));
+        assert_wound_outward(&mesh);
+    }
+
+    #[test]
+    fn solids_are_closed_and_wound_outward() {
+        // (rings + 1) * (slices + 1) vertices, with single triangles at the poles.
+        check_solid(Mesh::sphere(1.0, 8, 12).unwrap(), 9 * 13, 6 * 12 * 7);
+        check_solid(Mesh::cube(2.0).unwrap(), 24, 36);
+        // Side, then both caps with their centers.
+        check_solid(Mesh::cylinder(1.0, 2.0, 10).unwrap(), 2 * 11 + 2 * 11, 3 * (20 + 20));
+        check_solid(Mesh::cone(1.0, 2.0, 10).unwrap(), 2 * 11 + 11, 3 * (10 + 10));
+        check_solid(Mesh::torus(2.0, 0.5, 8, 12).unwrap(), 9 * 13, 6 * 8 * 12);
+        // Two hemispheres of 4 rings, with and without a cylinder between them.
+        check_solid(Mesh::capsule(0.5, 1.0, 4, 12).unwrap(), 10 * 13, 6 * 12 * 8);
+        check_solid(Mesh::capsule(0.5, 0.0, 4, 12).unwrap(), 9 * 13, 6 * 12 * 7);
+    }
+
+    #[test]
+    fn icosphere_subdivides_every_face() {
+        for subdivisions in 0..4 {
+            let mesh = Mesh::icosphere(1.0, subdivisions).unwrap();
+            let faces = 20 * 4usize.pow(subdivisions);
+            assert_eq!(mesh.indices.len(), 3 * faces);
+            // Euler's formula, before the seam and poles are split.
+            assert_eq!(welded(&mesh).1, faces / 2 + 2);
+            assert!(is_closed(&mesh));
+            assert_wound_outward(&mesh);
+        }
+    }
+
+    #[test]
+    fn plane_is_open_and_faces_up() {
+        let mesh = Mesh::plane(2.0, 3.0, 4, 5).unwrap();
+        assert_eq!(mesh.vertex_count(), 5 * 6);
+        assert_eq!(mesh.indices.len(), 6 * 4 * 5);
+        assert!(!is_closed(&mesh));
+        assert_wound_outward(&mesh);
+        assert!(attribute(&mesh, "v_normal").iter().all(|n| *n == Vec3::new([0.0, 1.0, 0.0])));
+    }
+
+    #[test]
+    fn invalid_sizes_give_none() {
+        assert!(Mesh::sphere(1.0, 2, 8).is_none());
+        assert!(Mesh::cube(0.0).is_none());
+        assert!(Mesh::plane(1.0, 1.0, 0, 1).is_none());
+        assert!(Mesh::cylinder(1.0, 1.0, 2).is_none());
+        assert!(Mesh::torus(0.5, 1.0, 8, 8).is_none());
+        assert!(Mesh::icosphere(-1.0, 1).is_none());
+    }
+
+    #[test]
+    fn sphere_uvs_are_equirectangular() {
+        let radius = 2.0;
+        let mesh = Mesh::sphere(radius, 8, 12).unwrap();
+        let positions = attribute(&mesh, "v_position");
+        let uvs = attribute(&mesh, "v_uv");
+
+        for (p, uv) in positions.iter().zip(&uvs) {
+            let [u, v, _] = uv.data;
+            assert!((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v), "uv {:?}", uv);
+            // v = 0 at the north pole and 1 at the south pole.
+            assert!((v - (p.data[1] / radius).clamp(-1.0, 1.0).acos() / PI).abs() < 1e-5, "{:?} at v {}", p, v);
+
+            if p.data[0].hypot(p.data[2]) > 1e-4 {
+                let angle = (-p.data[2]).atan2(p.data[0]).rem_euclid(2.0 * PI) / (2.0 * PI);
+                // The seam repeats u = 0 as u = 1.
+                let u = if u == 1.0 { 0.0 } else { u };
+                assert!((angle - u).abs() < 1e-5 || (angle - 1.0 - u).abs() < 1e-5, "{:?} at u {}", p, u);
+            }
+        }
+        assert!(uvs.iter().any(|uv| uv.data[1] == 0.0) && uvs.iter().any(|uv| uv.data[1] == 1.0));
+    }
+
+    #[test]
+    fn smooth_normals_match_the_sphere() {
+        let mut mesh = Mesh::sphere(1.0, 32, 48).unwrap();
+        let original = mesh.clone();
+        mesh.compute_normals(NormalMode::Smooth);
+
+        assert_eq!(mesh.layout, original.layout);
+        assert_eq!(mesh.indices, original.indices);
+        let normals = attribute(&mesh, "v_normal");
+        for (p, n) in attribute(&mesh, "v_position").iter().zip(&normals) {
+            assert!(p.normalize().dot(n) > 0.999, "{:?} at {:?}", n, p);
+        }
+        // Vertices split along the seam still share a normal, here on the
+        // equator's row of 49.
+        assert_eq!(normals[16 * 49], normals[16 * 49 + 48]);
+    }
+
+    #[test]
+    fn flat_normals_unindex_the_mesh() {
+        let mut mesh = Mesh::cube(2.0).unwrap();
+        let original = mesh.clone();
+        mesh.compute_normals(NormalMode::Flat);
+
+        assert!(!mesh.is_indexed());
+        assert_eq!(mesh.vertex_count(), original.indices.len());
+        assert_eq!(attribute(&mesh, "v_position"), original.indices.iter()
+            .map(|&i| attribute(&original, "v_position")[i as usize])
+            .collect::<Vec<_>>());
+        // The cube's own normals are already the face normals.
+        assert_eq!(attribute(&mesh, "v_normal"), original.indices.iter()
+            .map(|&i| attribute(&original, "v_normal")[i as usize])
+            .collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn computing_normals_adds_them_to_the_layout() {
+        // Counter-clockwise in the XY plane, so facing +Z.
+        let mut mesh = Mesh::new(vec![
+            0.0, 0.0, 0.0, 0.0, 1.0,
+            1.0, 0.0, 0.0, 1.0, 1.0,
+            0.0, 1.0, 0.0, 0.0, 0.0,
+        ]);
+        mesh.compute_normals(NormalMode::Smooth);
+        assert_eq!(mesh.layout, VertexLayout::position_uv_normal());
+        assert_eq!(mesh.vertices[..8], [0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(mesh.vertices[8..11], [1.0, 0.0, 0.0]);
+        assert!(attribute(&mesh, "v_normal").iter().all(|n| *n == Vec3::new([0.0, 0.0, 1.0])));
+
+        // A packed byte normal in the middle of the vertex moves to the end as floats.
+        let layout = VertexLayout::empty()
+            .with("v_position", 3, AttributeType::Float, false)
+            .with("v_normal", 4, AttributeType::Byte, true)
+            .with("v_uv", 2, AttributeType::Float, false);
+        let mut mesh = Mesh::with_layout(vec![
+            0.0, 0.0, 0.0, 0.0, 0.25, 0.5,
+            1.0, 0.0, 0.0, 0.0, 0.75, 0.5,
+            0.0, 1.0, 0.0, 0.0, 0.25, 1.0,
+        ], vec![], layout);
+        mesh.compute_normals(NormalMode::Flat);
+        assert_eq!(mesh.layout, VertexLayout::position_uv_normal());
+        assert_eq!(mesh.vertices[8..16], [1.0, 0.0, 0.0, 0.75, 0.5, 0.0, 0.0, 1.0]);
+
+        // Nothing to compute normals from.
+        let layout = VertexLayout::empty().with("v_uv", 2, AttributeType::Float, false);
+        let mut mesh = Mesh::with_layout(vec![0.0; 6], vec![], layout.clone());
+        mesh.compute_normals(NormalMode::Smooth);
+        assert_eq!(mesh.layout, layout);
+    }
+}
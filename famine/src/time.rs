@@ -0,0 +1,138 @@
+// Frame timing. Backends read their own clock (Instant, performance.now(),
+// a fixed step in tests) and feed the timestamps to a `FrameClock`.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameInfo {
+    // Seconds since the previous frame.
+    pub delta: f32,
+    // Seconds since the first frame.
+    pub time: f64,
+    // Number of frames before this one.
+    pub index: u64,
+}
+
+#[derive(Default)]
+pub struct FrameClock {
+    start: Option<f64>,
+    last: f64,
+    index: u64,
+}
+
+impl FrameClock {
+    pub fn new() -> Self {
+        FrameClock::default()
+    }
+
+    // `now` is a monotonic timestamp in seconds with any origin.
+    pub fn tick(&mut self, now: f64) -> FrameInfo {
+        let start = *self.start.get_or_insert(now);
+        let time = now - start;
+        let frame = FrameInfo {
+            delta: if self.index == 0 { 0.0 } else { (time - self.last).max(0.0) as f32 },
+            time,
+            index: self.index,
+        };
+        self.last = time;
+        self.index += 1;
+        frame
+    }
+}
+
+pub trait Simulation {
+    fn simulate(&mut self, dt: f32);
+    // `alpha` is how far real time has moved past the last simulated step,
+    // as a fraction of a step, for interpolating between the last two states.
+    fn render(&mut self, alpha: f32);
+}
+
+// Accumulates frame time and spends it in fixed size simulation steps.
+pub struct FixedTimestep {
+    pub step: f32,
+    // Steps allowed per frame before the remaining backlog is dropped,
+    // so a long stall doesn't turn into a long catch up.
+    pub max_steps: u32,
+    accumulator: f32,
+}
+
+impl FixedTimestep {
+    // Panics unless `step` is positive and finite.
+    pub fn new(step: f32) -> Self {
+        assert!(step > 0.0 && step.is_finite(), "Fixed timestep of {} is not a positive duration", step);
+        FixedTimestep { step, max_steps: 8, accumulator: 0.0 }
+    }
+
+    // Calls `simulate` zero or more times and returns the interpolation alpha.
+    pub fn advance(&mut self, delta: f32, mut simulate: impl FnMut(f32)) -> f32 {
+        self.accumulator += delta.max(0.0);
+
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            if steps == self.max_steps {
+                self.accumulator %= self.step;
+                break;
+            }
+            simulate(self.step);
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+
+        self.alpha()
+    }
+
+    pub fn alpha(&self) -> f32 {
+        self.accumulator / self.step
+    }
+
+    pub fn frame<S: Simulation>(&mut self, frame: &FrameInfo, simulation: &mut S) {
+        let alpha = self.advance(frame.delta, |dt| simulation.simulate(dt));
+        simulation.render(alpha);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEP: f32 = 0.25;
+
+    fn count_steps(timestep: &mut FixedTimestep, delta: f32) -> (u32, f32) {
+        let mut steps = 0;
+        let alpha = timestep.advance(delta, |dt| {
+            assert_eq!(dt, STEP);
+            steps += 1;
+        });
+        (steps, alpha)
+    }
+
+    #[test]
+    fn spends_time_in_whole_steps() {
+        let mut timestep = FixedTimestep::new(STEP);
+        assert_eq!(count_steps(&mut timestep, 1.0), (4, 0.0));
+        assert_eq!(count_steps(&mut timestep, 0.125), (0, 0.5));
+        assert_eq!(count_steps(&mut timestep, 0.1875), (1, 0.25));
+        assert_eq!(timestep.alpha(), 0.25);
+        // Negative deltas are ignored.
+        assert_eq!(count_steps(&mut timestep, -1.0), (0, 0.25));
+    }
+
+    #[test]
+    fn drops_the_backlog_past_max_steps() {
+        let mut timestep = FixedTimestep::new(STEP);
+        timestep.max_steps = 3;
+        // 40 steps and a half are due, 3 are taken and the half is kept.
+        assert_eq!(count_steps(&mut timestep, 10.125), (3, 0.5));
+        assert_eq!(count_steps(&mut timestep, 0.125), (1, 0.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_zero_steps() {
+        FixedTimestep::new(0.0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn rejects_infinite_steps() {
+        FixedTimestep::new(f32::INFINITY);
+    }
+}
@@ -0,0 +1,255 @@
+// Translation, rotation and scale, applied in the order scale, rotate,
+// translate. Building model matrices from these avoids getting the order
+// of matrix products wrong by hand.
+
+use crate::linalg::{Mat4, Quat, Vec3};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Transform {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3 { data: [1.0; 3] },
+    };
+
+    pub fn new(translation: Vec3, rotation: Quat, scale: Vec3) -> Self {
+        Transform { translation, rotation, scale }
+    }
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Transform { translation, ..Transform::IDENTITY }
+    }
+
+    pub fn from_rotation(rotation: Quat) -> Self {
+        Transform { rotation, ..Transform::IDENTITY }
+    }
+
+    pub fn from_scale(scale: Vec3) -> Self {
+        Transform { scale, ..Transform::IDENTITY }
+    }
+
+    // Splits an affine matrix back into its parts. Shear and projection can't
+    // be represented and are lost. A mirroring matrix comes back with a
+    // negative x scale. None when a column is zero.
+    pub fn from_mat4(m: &Mat4) -> Option<Self> {
+        let column = |j| Vec3::new([m.get(0, j), m.get(1, j), m.get(2, j)]);
+        let mut scale = Vec3::new([column(0).length(), column(1).length(), column(2).length()]);
+        if scale.data.iter().any(|s| !s.is_normal()) {
+            return None;
+        }
+        if m.upper_left().determinant() < 0.0 {
+            scale[0] = -scale[0];
+        }
+
+        let mut rotation = Mat4::identity();
+        for j in 0..3 {
+            let axis = column(j) / scale[j];
+            for i in 0..3 {
+                rotation.set(i, j, axis[i]);
+            }
+        }
+
+        Some(Transform {
+            translation: column(3),
+            rotation: Quat::from_mat4(&rotation),
+            scale,
+        })
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        let mut res = self.rotation.to_mat4();
+        for j in 0..3 {
+            for i in 0..3 {
+                res.set(i, j, res.get(i, j) * self.scale[j]);
+            }
+            res.set(j, 3, self.translation[j]);
+        }
+        res
+    }
+
+    pub fn transform_point(&self, point: &Vec3) -> Vec3 {
+        self.rotation.rotate(&(*point * self.scale)) + self.translation
+    }
+
+    pub fn transform_vector(&self, vector: &Vec3) -> Vec3 {
+        self.rotation.rotate(&(*vector * self.scale))
+    }
+
+    // `child` placed inside `self`, so `parent.compose(&child).matrix()` is
+    // `parent.matrix() * child.matrix()` in GLSL order. Exact unless `self`
+    // has non-uniform scale and `child` is rotated, which would need shear;
+    // use `TransformHierarchy` or the matrices directly for that case.
+    pub fn compose(&self, child: &Transform) -> Transform {
+        Transform {
+            translation: self.transform_point(&child.translation),
+            rotation: (self.rotation * child.rotation).normalize(),
+            scale: self.scale * child.scale,
+        }
+    }
+
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        Transform {
+            translation: self.translation.lerp(&other.translation, t),
+            rotation: self.rotation.slerp(&other.rotation, t),
+            scale: self.scale.lerp(&other.scale, t),
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::IDENTITY
+    }
+}
+
+impl From<&Transform> for Mat4 {
+    fn from(transform: &Transform) -> Self {
+        transform.matrix()
+    }
+}
+
+pub type NodeId = usize;
+
+struct Node {
+    local: Transform,
+    parent: Option<NodeId>,
+}
+
+// A forest of transforms whose world matrices are each parent's world
+// matrix times the local matrix. Parents always exist before their
+// children, so one pass in insertion order updates everything.
+#[derive(Default)]
+pub struct TransformHierarchy {
+    nodes: Vec<Node>,
+    world: Vec<Mat4>,
+}
+
+impl TransformHierarchy {
+    pub fn new() -> Self {
+        TransformHierarchy::default()
+    }
+
+    // Panics if `parent` isn't a node of this hierarchy.
+    pub fn add(&mut self, local: Transform, parent: Option<NodeId>) -> NodeId {
+        if let Some(parent) = parent {
+            assert!(parent < self.nodes.len(), "Transform parent {} does not exist", parent);
+        }
+        self.nodes.push(Node { local, parent });
+        self.world.push(Mat4::identity());
+        self.nodes.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.nodes[id].parent
+    }
+
+    pub fn local(&self, id: NodeId) -> &Transform {
+        &self.nodes[id].local
+    }
+
+    pub fn local_mut(&mut self, id: NodeId) -> &mut Transform {
+        &mut self.nodes[id].local
+    }
+
+    // Recomputes every world matrix from the local transforms.
+    pub fn update(&mut self) {
+        for id in 0..self.nodes.len() {
+            let local = self.nodes[id].local.matrix();
+            self.world[id] = match self.nodes[id].parent {
+                Some(parent) => self.world[parent] * local,
+                None => local,
+            };
+        }
+    }
+
+    // As of the last `update`.
+    pub fn world_matrix(&self, id: NodeId) -> &Mat4 {
+        &self.world[id]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::linalg::Vec4;
+
+    use super::*;
+
+    fn assert_close(a: &Mat4, b: &Mat4) {
+        for (x, y) in a.data.iter().zip(b.data.iter()) {
+            assert!((x - y).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn matrix_round_trip() {
+        let transforms = [
+            Transform::IDENTITY,
+            Transform::new(
+                Vec3::new([1.0, -2.0, 3.0]),
+                Quat::from_euler(0.3, 1.1, -0.6),
+                Vec3::new([2.0, 0.5, 3.0]),
+            ),
+            Transform::new(Vec3::new([0.0, 4.0, 0.0]), Quat::from_euler(2.0, 0.0, 1.0), Vec3::new([-1.5, 1.0, 1.0])),
+        ];
+
+        for t in transforms {
+            let back = Transform::from_mat4(&t.matrix()).unwrap();
+            assert_close(&back.matrix(), &t.matrix());
+            assert!((back.translation - t.translation).length() < 1e-5);
+            assert!((back.rotation.dot(&t.rotation).abs() - 1.0).abs() < 1e-5);
+        }
+
+        assert!(Transform::from_mat4(&Mat4::scale(1.0, 0.0, 1.0)).is_none());
+    }
+
+    #[test]
+    fn matrix_matches_transform_point() {
+        let t = Transform::new(Vec3::new([1.0, 2.0, 3.0]), Quat::from_euler(0.5, -0.2, 0.9), Vec3::new([2.0, 1.0, 0.5]));
+        let point = Vec3::new([0.3, -1.0, 2.0]);
+        let transformed = (t.matrix() * point.extend(1.0)).truncate();
+        assert!((transformed - t.transform_point(&point)).length() < 1e-5);
+    }
+
+    #[test]
+    fn hierarchy_propagates_world_matrices() {
+        let mut hierarchy = TransformHierarchy::new();
+        let root = hierarchy.add(Transform::from_translation(Vec3::new([10.0, 0.0, 0.0])), None);
+        let arm = hierarchy.add(
+            Transform::new(Vec3::new([0.0, 1.0, 0.0]), Quat::from_euler(0.0, 0.0, 0.4), Vec3::new([2.0, 1.0, 1.0])),
+            Some(root),
+        );
+        let hand = hierarchy.add(Transform::from_translation(Vec3::new([1.0, 0.0, 0.0])), Some(arm));
+        let other = hierarchy.add(Transform::from_scale(Vec3::splat(3.0)), None);
+        hierarchy.update();
+
+        let root_matrix = hierarchy.local(root).matrix();
+        let arm_matrix = root_matrix * hierarchy.local(arm).matrix();
+        let hand_matrix = arm_matrix * hierarchy.local(hand).matrix();
+        assert_close(hierarchy.world_matrix(root), &root_matrix);
+        assert_close(hierarchy.world_matrix(arm), &arm_matrix);
+        assert_close(hierarchy.world_matrix(hand), &hand_matrix);
+        assert_close(hierarchy.world_matrix(other), &hierarchy.local(other).matrix());
+
+        // Moving a parent moves its descendants on the next update.
+        hierarchy.local_mut(root).translation = Vec3::ZERO;
+        hierarchy.update();
+        let origin = Vec4::new([0.0, 0.0, 0.0, 1.0]);
+        let hand_position = (*hierarchy.world_matrix(hand) * origin).truncate();
+        let expected = hierarchy.local(arm).transform_point(&Vec3::new([1.0, 0.0, 0.0]));
+        assert!((hand_position - expected).length() < 1e-5);
+    }
+}
@@ -0,0 +1,236 @@
+// How the floats of `Mesh::vertices` split into shader attributes. The
+// attributes of a vertex are packed in order without padding and each one
+// fills whole 4 byte words, so e.g. an rgba color can be four normalized
+// unsigned bytes sharing a single float slot.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttributeType {
+    Float,
+    Byte,
+    UnsignedByte,
+    Short,
+    UnsignedShort,
+}
+
+impl AttributeType {
+    pub fn size(&self) -> usize {
+        match self {
+            AttributeType::Float => 4,
+            AttributeType::Byte | AttributeType::UnsignedByte => 1,
+            AttributeType::Short | AttributeType::UnsignedShort => 2,
+        }
+    }
+
+    // The type argument of vertexAttribPointer.
+    pub fn gl_enum(&self) -> u32 {
+        match self {
+            AttributeType::Float => 0x1406,
+            AttributeType::Byte => 0x1400,
+            AttributeType::UnsignedByte => 0x1401,
+            AttributeType::Short => 0x1402,
+            AttributeType::UnsignedShort => 0x1403,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexAttribute {
+    pub name: String,
+    pub components: usize,
+    pub kind: AttributeType,
+    // Integer values are mapped to [0, 1] or [-1, 1] instead of converted.
+    pub normalized: bool,
+}
+
+impl VertexAttribute {
+    pub fn size(&self) -> usize {
+        self.components * self.kind.size()
+    }
+
+    // GL's conversion to float, for backends without a GPU.
+    fn decode(&self, bytes: &[u8]) -> f32 {
+        let (value, max) = match self.kind {
+            AttributeType::Float => return f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+            AttributeType::Byte => (bytes[0] as i8 as f32, 127.0),
+            AttributeType::UnsignedByte => (bytes[0] as f32, 255.0),
+            AttributeType::Short => (i16::from_ne_bytes([bytes[0], bytes[1]]) as f32, 32767.0),
+            AttributeType::UnsignedShort => (u16::from_ne_bytes([bytes[0], bytes[1]]) as f32, 65535.0),
+        };
+
+        if self.normalized { (value / max).max(-1.0) } else { value }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexLayout {
+    attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayout {
+    // A layout without attributes, to add to with `with`.
+    pub fn empty() -> Self {
+        VertexLayout { attributes: vec![] }
+    }
+
+    // Position (xyz) and uv, the layout of `Mesh::new` and text.
+    pub fn position_uv() -> Self {
+        VertexLayout::empty()
+            .with("v_position", 3, AttributeType::Float, false)
+            .with("v_uv", 2, AttributeType::Float, false)
+    }
+
+    // Position, uv and normal (xyz), the layout of the primitives.
+    pub fn position_uv_normal() -> Self {
+        VertexLayout::position_uv().with("v_normal", 3, AttributeType::Float, false)
+    }
+
+    // Panics if the attribute doesn't have 1 to 4 components filling whole
+    // words, or if its name is already used.
+    pub fn with(mut self, name: &str, components: usize, kind: AttributeType, normalized: bool) -> Self {
+        let attribute = VertexAttribute { name: name.to_string(), components, kind, normalized };
+        assert!((1..=4).contains(&components), "Vertex attribute {} has {} components", name, components);
+        assert!(attribute.size().is_multiple_of(4), "Vertex attribute {} does not fill whole words", name);
+        assert!(self.find(name).is_none(), "Vertex attribute {} is already in the layout", name);
+
+        self.attributes.push(attribute);
+        self
+    }
+
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    // Bytes per vertex.
+    pub fn stride(&self) -> usize {
+        self.attributes.iter().map(VertexAttribute::size).sum()
+    }
+
+    // Floats per vertex in `Mesh::vertices`.
+    pub fn vertex_size(&self) -> usize {
+        self.stride() / 4
+    }
+
+    // Each attribute with its byte offset into the vertex.
+    pub fn offsets(&self) -> impl Iterator<Item = (usize, &VertexAttribute)> {
+        self.attributes.iter().scan(0, |offset, attribute| {
+            let current = *offset;
+            *offset += attribute.size();
+            Some((current, attribute))
+        })
+    }
+
+    pub fn find(&self, name: &str) -> Option<(usize, &VertexAttribute)> {
+        self.offsets().find(|(_, attribute)| attribute.name == name)
+    }
+
+    // The attribute `name` of one vertex as the shader would see it, with
+    // missing components filled in from (0, 0, 0, 1).
+    pub fn read(&self, vertex: &[f32], name: &str) -> Option<[f32; 4]> {
+        let (offset, attribute) = self.find(name)?;
+        let bytes: Vec<u8> = vertex.iter().flat_map(|v| v.to_ne_bytes()).collect();
+
+        let mut value = [0.0, 0.0, 0.0, 1.0];
+        for (c, component) in value.iter_mut().take(attribute.components).enumerate() {
+            *component = attribute.decode(&bytes[offset + c * attribute.kind.size()..]);
+        }
+        Some(value)
+    }
+
+    // Checks that every attribute a shader reads is provided. Attributes the
+    // shader doesn't use are fine.
+    pub fn validate<'a>(&self, shader_attributes: impl IntoIterator<Item = &'a str>) -> Result<(), String> {
+        let missing: Vec<&str> = shader_attributes.into_iter()
+            .filter(|name| self.find(name).is_none())
+            .collect();
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Mesh is missing the shader attributes {}", missing.join(", ")))
+        }
+    }
+}
+
+impl Default for VertexLayout {
+    fn default() -> Self {
+        VertexLayout::position_uv()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Position, an rgba color of normalized bytes and a pair of shorts.
+    fn packed() -> VertexLayout {
+        VertexLayout::empty()
+            .with("v_position", 3, AttributeType::Float, false)
+            .with("v_color", 4, AttributeType::UnsignedByte, true)
+            .with("v_offset", 2, AttributeType::Short, true)
+    }
+
+    fn word(bytes: [u8; 4]) -> f32 {
+        f32::from_ne_bytes(bytes)
+    }
+
+    #[test]
+    fn attributes_are_packed_in_order() {
+        let layout = packed();
+        let offsets: Vec<(usize, &str)> = layout.offsets().map(|(offset, a)| (offset, a.name.as_str())).collect();
+        assert_eq!(offsets, [(0, "v_position"), (12, "v_color"), (16, "v_offset")]);
+        assert_eq!(layout.stride(), 20);
+        assert_eq!(layout.vertex_size(), 5);
+        assert_eq!(layout.find("v_offset").map(|(offset, _)| offset), Some(16));
+        assert!(layout.find("v_normal").is_none());
+    }
+
+    #[test]
+    fn read_converts_like_gl() {
+        let layout = packed();
+        let [low, high] = (-32767i16).to_ne_bytes();
+        let [low2, high2] = 16384i16.to_ne_bytes();
+        let vertex = [1.0, 2.0, 3.0, word([255, 0, 51, 128]), word([low, high, low2, high2])];
+
+        assert_eq!(layout.read(&vertex, "v_position"), Some([1.0, 2.0, 3.0, 1.0]));
+        assert_eq!(layout.read(&vertex, "v_color"), Some([1.0, 0.0, 0.2, 128.0 / 255.0]));
+        assert_eq!(layout.read(&vertex, "v_offset"), Some([-1.0, 16384.0 / 32767.0, 0.0, 1.0]));
+        assert_eq!(layout.read(&vertex, "v_normal"), None);
+
+        // Unnormalized integers keep their values, and the most negative
+        // normalized one clamps to -1.
+        let layout = VertexLayout::empty()
+            .with("v_index", 4, AttributeType::UnsignedByte, false)
+            .with("v_direction", 4, AttributeType::Byte, true);
+        let vertex = [word([0, 1, 2, 255]), word([128, 127, 0, 0])];
+        assert_eq!(layout.read(&vertex, "v_index"), Some([0.0, 1.0, 2.0, 255.0]));
+        assert_eq!(layout.read(&vertex, "v_direction"), Some([-1.0, 1.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn validate_lists_the_missing_attributes() {
+        let layout = VertexLayout::position_uv();
+        assert_eq!(layout.validate(["v_uv", "v_position"]), Ok(()));
+        assert_eq!(
+            layout.validate(["v_position", "v_normal", "v_color"]),
+            Err("Mesh is missing the shader attributes v_normal, v_color".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Vertex attribute v_weights has 5 components")]
+    fn with_rejects_too_many_components() {
+        VertexLayout::empty().with("v_weights", 5, AttributeType::Float, false);
+    }
+
+    #[test]
+    #[should_panic(expected = "Vertex attribute v_color does not fill whole words")]
+    fn with_rejects_partial_words() {
+        VertexLayout::empty().with("v_color", 3, AttributeType::UnsignedByte, true);
+    }
+
+    #[test]
+    #[should_panic(expected = "Vertex attribute v_uv is already in the layout")]
+    fn with_rejects_duplicate_names() {
+        VertexLayout::position_uv().with("v_uv", 2, AttributeType::Float, false);
+    }
+}
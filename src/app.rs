@@ -1,13 +1,13 @@
 extern crate famine;
 
-use famine::{linalg::Mat4, Application, Color, ContextType, Mesh};
+use famine::{linalg::{Mat4, Quat, Vec3}, time::FrameInfo, transform::Transform, Application, Color, ContextType, Mesh};
 
 pub struct App<Context: ContextType> {
     pub ctx: Context,
     pub basic_shader: Context::Shader,
     pub vmp_matrix: Mat4,
     pub texture: Context::Texture,
-    pub mesh: Mesh,
+    pub mesh: Context::MeshBuffer,
     pub rotation: f32,
 }    
 
@@ -67,7 +67,7 @@ impl<Context: ContextType> Application<Context> for App<Context> {
             255, 0, 0,   255
         ]);
         
-        let mesh = Mesh::sphere(0.5, 30, 30).unwrap();
+        let mesh = ctx.upload_mesh(&Mesh::sphere(0.5, 30, 30).unwrap());
 
         App {
             ctx,
@@ -79,19 +79,23 @@ impl<Context: ContextType> Application<Context> for App<Context> {
         }
     }
 
-    async fn update(&mut self) {
-        let rotation_matrix_0 = Mat4::rotate_y(self.rotation);
-        let rotation_matrix_1 = Mat4::rotate_x(self.rotation);
+    async fn update(&mut self, frame: FrameInfo) {
+        // Quat angles turn the opposite way to `Mat4::rotate_y/x`, which the
+        // spin was first written with.
+        let model = Transform::from_rotation(
+            Quat::from_axis_angle(&Vec3::new([0.0, 1.0, 0.0]), -self.rotation)
+                * Quat::from_axis_angle(&Vec3::new([1.0, 0.0, 0.0]), -self.rotation),
+        );
         let scale_matrix = Mat4::scale(self.ctx.display_height() as f32 / self.ctx.display_width() as f32, 1.0, 1.0);
-        self.vmp_matrix = rotation_matrix_1.mul(&rotation_matrix_0).mul(&scale_matrix);
+        self.vmp_matrix = scale_matrix * model.matrix();
 
-        self.rotation += 0.02;
+        self.rotation += 0.6 * frame.delta;
 
         self.ctx.clear(0.02, 0.05, 0.2, 1.0);
-        self.ctx.use_texture(&mut self.texture);
+        self.ctx.use_texture(&self.texture);
         self.ctx.use_shader(&self.basic_shader);
         self.ctx.set_uniform_mat4(&self.basic_shader, "u_ViewModelProjection", &self.vmp_matrix);
-        self.ctx.draw_mesh(&self.mesh);
+        self.ctx.draw_mesh_buffer(&self.mesh);
 
         let rot_str = format!("{}", self.rotation);
         let text_w = rot_str.len() as f32 * 0.05;